@@ -0,0 +1,100 @@
+//! Writes a synthetic Tana export to stdout, for measuring the converter on
+//! large workspaces.
+//!
+//! ```sh
+//! cargo run --release --example large_fixture -- 500000 > large.json
+//! /usr/bin/time -v target/release/tana-to-markdown export large.json out
+//! ```
+
+use std::io::{self, BufWriter, Write};
+
+use serde_json::json;
+
+fn main() -> io::Result<()> {
+    let pages: usize = std::env::args()
+        .nth(1)
+        .and_then(|arg| arg.parse().ok())
+        .unwrap_or(100_000);
+    let children_per_page = 8;
+
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    let mut first = true;
+    let mut write_doc = |out: &mut BufWriter<_>, doc: serde_json::Value| -> io::Result<()> {
+        if !first {
+            out.write_all(b",\n")?;
+        }
+        first = false;
+        serde_json::to_writer(&mut *out, &doc)?;
+        Ok(())
+    };
+
+    out.write_all(b"{\"formatVersion\":1,\"docs\":[\n")?;
+
+    let created = 1_700_000_000_000u64;
+    write_doc(
+        &mut out,
+        json!({"id": "SYS_A13", "props": {"created": created, "name": "tags"}}),
+    )?;
+    write_doc(
+        &mut out,
+        json!({"id": "SYS_T01", "props": {"created": created, "name": "supertag"}}),
+    )?;
+    write_doc(
+        &mut out,
+        json!({"id": "tag", "props": {"created": created, "name": "note", "_metaNodeId": "tag_meta"}}),
+    )?;
+    write_doc(
+        &mut out,
+        json!({"id": "tag_meta", "props": {"created": created, "_ownerId": "tag"}, "children": ["tag_tuple"]}),
+    )?;
+    write_doc(
+        &mut out,
+        json!({"id": "tag_tuple", "props": {"created": created, "_docType": "tuple", "_ownerId": "tag_meta"}, "children": ["SYS_A13", "SYS_T01"]}),
+    )?;
+
+    for page in 0..pages {
+        let id = format!("page{}", page);
+        let children: Vec<String> = (0..children_per_page)
+            .map(|child| format!("{}_{}", id, child))
+            .collect();
+
+        write_doc(
+            &mut out,
+            json!({
+                "id": id,
+                "props": {"created": created + page as u64, "name": format!("Page {}", page), "_metaNodeId": format!("{}_meta", id)},
+                "modifiedTs": [created + page as u64],
+                "children": children,
+            }),
+        )?;
+        write_doc(
+            &mut out,
+            json!({"id": format!("{}_meta", id), "props": {"created": created, "_ownerId": id}, "children": [format!("{}_tags", id)]}),
+        )?;
+        write_doc(
+            &mut out,
+            json!({"id": format!("{}_tags", id), "props": {"created": created, "_docType": "tuple", "_ownerId": format!("{}_meta", id)}, "children": ["SYS_A13", "tag"]}),
+        )?;
+
+        for (index, child) in children.iter().enumerate() {
+            let name = match index {
+                0 if page > 0 => format!(
+                    "Follows <span data-inlineref-node=\"page{}\"></span>",
+                    page - 1
+                ),
+                _ => format!(
+                    "Line {} of page {} with some text to make it realistic",
+                    index, page
+                ),
+            };
+            write_doc(
+                &mut out,
+                json!({"id": child, "props": {"created": created, "name": name, "_ownerId": id}}),
+            )?;
+        }
+    }
+
+    out.write_all(b"\n]}\n")?;
+    out.flush()
+}
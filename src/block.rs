@@ -1,118 +1,87 @@
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{cell::RefCell, rc::Rc};
 
-use crate::node::*;
+use crate::{date::Date, node::*};
 
 pub type BlockRef = Rc<RefCell<Block>>;
 
+pub enum Inline {
+    Text(String),
+    Ref(BlockRef),
+    /// A reference to a calendar date.
+    Date(Date),
+}
+
+/// A node that pages are filed under. The `PageIndex` names its folder, or
+/// uses the page's own name when the node is itself a page.
+#[derive(Clone)]
+pub struct Folder {
+    pub id: String,
+    pub title: String,
+}
+
 pub struct Block {
     pub id: String,
     pub title: String,
+    pub inlines: Vec<Inline>,
     pub description: Option<String>,
     pub tags: Vec<String>,
-    pub metadata: HashMap<String, Vec<BlockRef>>,
+    /// Fields in the order their tuples appear in the Tana export.
+    pub metadata: Vec<(String, Vec<BlockRef>)>,
     pub children: RefCell<Vec<BlockRef>>,
     pub doc_type: DocType,
+    pub language: Option<String>,
+    pub search: Option<Vec<SearchTerm>>,
+    /// `Some(done)` for checkbox nodes, `None` for everything else.
+    pub todo: Option<bool>,
+    /// The date of a Tana calendar day node, which is exported as a journal
+    /// page.
+    pub journal: Option<Date>,
+    /// Whether the block is written as a page of its own, as decided by the
+    /// `PageRules` of the export.
+    pub page: bool,
+    /// Nodes the page's file is filed under, outermost first.
+    pub folders: Vec<Folder>,
+    /// The block shown in place of this one: the source of a Tana reference
+    /// node, or the original of a node placed under more than one parent.
+    pub source: Option<BlockRef>,
+    pub created: u64,
+    /// When the block last changed, falling back to its creation time.
+    pub modified: u64,
+    /// How many inline references and reference nodes point at the block.
     pub ref_count: usize,
 }
 
 impl Block {
-    pub fn get_link(&self) -> String {
-        format!("[[{}]]", self.title)
-    }
-
     pub fn get_children(&self) -> Vec<BlockRef> {
-        self.children
-            .borrow()
-            .iter()
-            .map(|child| Rc::clone(child))
-            .collect()
+        self.children.borrow().iter().map(Rc::clone).collect()
     }
 
     pub fn is_page(&self) -> bool {
+        self.page
+    }
+
+    /// Whether the block can be a page at all: text with a title that isn't
+    /// part of the schema.
+    pub fn can_be_page(&self) -> bool {
         let is_field = self.tags.contains(&String::from("field-definition"));
         let is_supertag = self.tags.contains(&String::from("supertag"));
-        let title_less = self.title.len() == 0;
-        if is_field || is_supertag || title_less {
-            return false;
-        }
-
-        let has_tag = self.tags.iter().filter(|tag| tag != &"todo").count() > 0;
-        let has_field = self.metadata.len() > 0;
+        let title_less = self.title.is_empty();
 
-        match self.doc_type {
-            DocType::Text => has_tag || has_field,
-            _ => false,
-        }
+        matches!(self.doc_type, DocType::Text) && !is_field && !is_supertag && !title_less
     }
 
-    pub fn get_content(&self, level: usize, extend: bool) -> Vec<String> {
-        let mut content = vec![];
-        let is_page = self.is_page();
-        let prefix = "  ".repeat(level);
-        let next_level = if is_page { level } else { level + 1 };
-
-        if is_page && !extend {
-            content.push(format!("{}- {}", prefix, self.get_link()));
-            return content;
-        }
-
-        if is_page {
-            content.push(format!("{}title:: {}", prefix, &self.title));
-        }
-
-        if self.tags.len() > 0 {
-            let tags = self
-                .tags
-                .iter()
-                .map(|tag| format!("#{}", &tag))
-                .collect::<Vec<String>>()
-                .join(" ");
+    /// The built-in page rule: blocks with a tag other than `todo` or with
+    /// fields.
+    pub fn looks_like_page(&self) -> bool {
+        let has_tag = self.tags.iter().any(|tag| tag != "todo");
+        let has_field = !self.metadata.is_empty();
 
-            if is_page {
-                content.push(format!("{}tags:: {}", prefix, tags));
-            } else {
-                content.push(format!("{}- {} {}", prefix, tags, &self.title))
-            }
-        } else if !is_page {
-            content.push(format!("{}- {}", prefix, &self.title))
-        }
-
-        if self.metadata.len() > 0 || self.description.is_some() {
-            let prefix = if is_page {
-                prefix
-            } else {
-                "  ".repeat(next_level)
-            };
-            content.push(format!("{}- Metadata", prefix));
-            if self.description.is_some() {
-                content.push(format!(
-                    "{}  - Description: {}",
-                    prefix,
-                    self.description.as_ref().unwrap()
-                ))
-            }
-
-            self.metadata.iter().for_each(|(key, values)| {
-                content.push(format!("{}  - {}", prefix, key));
-                let mut sub_contents: Vec<String> = values
-                    .iter()
-                    .map(|block| block.borrow().get_content(next_level, false))
-                    .flatten()
-                    .map(|str| format!("    {}", str))
-                    .collect();
-                content.append(&mut sub_contents);
-            })
-        }
-
-        let children = self.get_children();
-        let mut child_contents: Vec<String> = children
-            .iter()
-            .map(|block| block.borrow().get_content(next_level, false))
-            .flatten()
-            .map(|str| format!("{}", str))
-            .collect();
+        self.can_be_page() && (has_tag || has_field)
+    }
 
-        content.append(&mut child_contents);
-        content
+    /// Days of the calendar are journal pages as soon as anything was written
+    /// on them.
+    pub fn is_journal_page(&self) -> bool {
+        self.journal.is_some() && (!self.children.borrow().is_empty() || !self.metadata.is_empty())
     }
 }
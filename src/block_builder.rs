@@ -1,164 +1,782 @@
-use std::{cell::RefCell, collections::HashMap, io::Write, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::{BTreeSet, HashMap, HashSet},
+    path::PathBuf,
+    rc::Rc,
+    sync::LazyLock,
+};
 
 use regex::Regex;
 
-use crate::{block::*, node::*, node_builder::*};
+use crate::{
+    block::*,
+    config::{Filter, LinkOptions, PageRules},
+    date::Date,
+    error::{Error, NodeIssue, Result, Stage},
+    manifest::{content_hash, Manifest, ManifestEntry},
+    node::*,
+    node_builder::*,
+    page_index::PageIndex,
+    renderer::{RenderContext, Renderer},
+    report::Report,
+    sink::{PageSink, RenderedPage},
+};
+
+/// Inline references to nodes and dates inside a node name.
+static INLINE_REF: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new("<span data-inlineref-(node|date)=\"(.*?)\"></span>").unwrap());
 
 pub struct BlockBuilder {
     store: Rc<RefCell<HashMap<String, BlockRef>>>,
+    /// Block ids in the order they were built.
+    order: RefCell<Vec<String>>,
     node_builder: Rc<NodeBuilder>,
+    report: Rc<Report>,
+    link_options: LinkOptions,
 }
 
 impl BlockBuilder {
     pub fn new(node_builder: Rc<NodeBuilder>) -> BlockBuilder {
         BlockBuilder {
             store: Rc::new(RefCell::new(HashMap::new())),
+            order: RefCell::new(vec![]),
+            report: node_builder.get_report(),
             node_builder: Rc::clone(&node_builder),
+            link_options: LinkOptions::default(),
+        }
+    }
+
+    pub fn set_link_options(&mut self, link_options: LinkOptions) {
+        self.link_options = link_options;
+    }
+
+    pub fn build_blocks(&self) -> Result<()> {
+        for node in self.node_builder.get_nodes() {
+            if !node.borrow().is_in_trash()? && !node.borrow().is_sys_node() {
+                self.build_block(node)?;
+            }
         }
+
+        Ok(())
     }
 
-    pub fn build_blocks(&self) {
-        self.node_builder
-            .get_nodes()
+    /// Marks the blocks selected by `rules` as pages. Pages from the library,
+    /// or all pages for nested rules, are placed in folders following the
+    /// hierarchy they live in.
+    pub fn select_pages(&self, rules: &PageRules) -> Result<()> {
+        let workspace = self.node_builder.get_workspace()?;
+
+        for block in self.get_blocks() {
+            let id = block.borrow().id.clone();
+            if workspace.is_container(&id) {
+                block.borrow_mut().page = false;
+                continue;
+            }
+
+            let owners = self
+                .node_builder
+                .get_node(&id)?
+                .borrow()
+                .get_owner_chain()?;
+            let owner_id = owners.first().map(|owner| owner.borrow().id.clone());
+            let is_top_level =
+                owner_id.is_some() && (owner_id == workspace.root || owner_id == workspace.library);
+
+            let page = rules.matches(&block.borrow(), owners.len(), is_top_level);
+            block.borrow_mut().page = page;
+
+            let library_position = owners
+                .iter()
+                .position(|owner| Some(&owner.borrow().id) == workspace.library.as_ref());
+            let folder_owners: Vec<NodeRef> = match (library_position, rules.nested) {
+                (Some(position), _) => owners[..=position].to_vec(),
+                // The library keeps its own folder, other containers add none.
+                (None, true) => owners
+                    .iter()
+                    .filter(|owner| !workspace.is_container(&owner.borrow().id))
+                    .map(Rc::clone)
+                    .collect(),
+                (None, false) => vec![],
+            };
+            block.borrow_mut().folders = self.get_folders(&folder_owners);
+        }
+
+        Ok(())
+    }
+
+    /// Lists the folders for a chain of owners, nearest owner last in the
+    /// result. Owners without a title, like field tuples, add no folder.
+    fn get_folders(&self, owners: &[NodeRef]) -> Vec<Folder> {
+        owners
             .iter()
-            .filter(|node| !node.borrow().is_in_trash() && !node.borrow().is_sys_node())
-            .for_each(|node| {
-                self.build_block(Rc::clone(node));
+            .rev()
+            .filter_map(|owner| {
+                let id = owner.borrow().id.clone();
+                let title = match self.get_block(&id) {
+                    Ok(block) => block.borrow().title.clone(),
+                    Err(_) => owner.borrow().get_name().ok().flatten().unwrap_or_default(),
+                };
+                Some(Folder { id, title }).filter(|folder| !folder.title.is_empty())
             })
+            .collect()
+    }
+
+    /// Renders every page selected by `filter` and hands it to `sink`.
+    pub fn render_pages(
+        &self,
+        renderer: &dyn Renderer,
+        filter: &Filter,
+        sink: &mut dyn PageSink,
+    ) -> Result<()> {
+        let pages = self.get_filtered_pages(filter);
+        let page_index = PageIndex::new(&pages, renderer, &self.link_options);
+
+        pages.iter().try_for_each(|page| {
+            self.render_page(renderer, page, &page_index, sink)
+                .map(|_| ())
+        })
     }
 
-    pub fn write_pages(&self, output: &String) {
-        let output_path = std::path::Path::new(output);
+    /// Renders only the pages that changed since `previous` was written, and
+    /// removes the files of pages that are gone. `next` is the empty manifest
+    /// of this export, which tells whether `previous` was rendered the same
+    /// way. Returns it describing the output afterwards.
+    pub fn render_changed_pages(
+        &self,
+        renderer: &dyn Renderer,
+        filter: &Filter,
+        mut next: Manifest,
+        previous: &Manifest,
+        sink: &mut dyn PageSink,
+    ) -> Result<Manifest> {
+        let pages = self.get_filtered_pages(filter);
+        let page_index = PageIndex::new(&pages, renderer, &self.link_options);
+        let is_comparable = previous.has_same_settings(&next);
 
-        if !output_path.exists() {
-            std::fs::create_dir(output_path).unwrap();
+        for page in pages.iter() {
+            let id = page.borrow().id.clone();
+            let mut entry = self.get_manifest_entry(page, &page_index)?;
+
+            entry.hash = match is_comparable && previous.is_current(&id, &entry) {
+                true => previous.pages[&id].hash,
+                false => self.render_page(renderer, page, &page_index, sink)?,
+            };
+            next.pages.insert(id, entry);
         }
 
-        self.get_blocks()
-            .iter()
-            .filter(|block| block.borrow().is_page())
-            .for_each(|block| {
-                let filename = &block.borrow().title;
-                let mut filepath = std::path::Path::new(output).to_path_buf().join(filename);
-                filepath.set_extension("md");
+        for path in previous.get_removed_paths(&next) {
+            sink.remove_page(&path)?;
+        }
+
+        Ok(next)
+    }
+
+    fn get_filtered_pages(&self, filter: &Filter) -> Vec<BlockRef> {
+        self.get_pages()
+            .into_iter()
+            .filter(|page| filter.matches(&page.borrow()))
+            .collect()
+    }
+
+    /// Renders a single page into `sink` and returns the hash of its content.
+    fn render_page(
+        &self,
+        renderer: &dyn Renderer,
+        page: &BlockRef,
+        page_index: &PageIndex,
+        sink: &mut dyn PageSink,
+    ) -> Result<u64> {
+        let page = page.borrow();
+        let path = page_index
+            .get_path(&page.id)
+            .ok_or_else(|| Error::node(&page.id, NodeIssue::MissingNode))?;
 
-                let content = block.borrow().get_content(0, true).join("\n");
+        let ctx = RenderContext {
+            pages: page_index,
+            page_id: &page.id,
+            single_stream: sink.is_single_stream(),
+            links: &self.link_options,
+        };
+        let content = renderer.render_page(&page, &ctx).join("\n");
+        let hash = content_hash(content.as_bytes());
+
+        sink.write_page(RenderedPage {
+            id: page.id.clone(),
+            path: path.clone(),
+            content,
+        })?;
+        Ok(hash)
+    }
 
-                std::fs::File::create(filepath)
-                    .unwrap()
-                    .write(&content.as_bytes())
-                    .unwrap();
+    /// Describes what the rendered page depends on: every block shown on it,
+    /// including referenced ones, and the files of the pages it links to.
+    fn get_manifest_entry(&self, page: &BlockRef, page_index: &PageIndex) -> Result<ManifestEntry> {
+        let id = page.borrow().id.clone();
+        let path = page_index
+            .get_path(&id)
+            .ok_or_else(|| Error::node(&id, NodeIssue::MissingNode))?;
+
+        let mut visited = HashSet::new();
+        let mut modified = 0;
+        let mut links = BTreeSet::new();
+        collect_dependencies(page, page_index, &mut visited, &mut modified, &mut links);
+
+        // Blocks also change when they gain or lose the id references point
+        // at, which happens on other pages.
+        let mut blocks: BTreeSet<String> = visited
+            .into_iter()
+            .map(|block_id| match page_index.has_block_id(&block_id) {
+                true => format!("{}^", block_id),
+                false => block_id,
             })
+            .collect();
+
+        if self.link_options.backlinks.is_some() {
+            page_index.get_backlinks(&id).iter().for_each(|backlink| {
+                if let Some(path) = page_index.get_path(&backlink.page.borrow().id) {
+                    links.insert(path.clone());
+                }
+                if let Some(context) = &backlink.context {
+                    let context = context.borrow();
+                    modified = modified.max(context.modified);
+                    blocks.insert(format!("<{}", context.id));
+                }
+            });
+        }
+
+        let blocks = blocks.into_iter().collect::<Vec<String>>().join("\n");
+        Ok(ManifestEntry {
+            path: path.clone(),
+            modified,
+            links: links.into_iter().collect(),
+            blocks: content_hash(blocks.as_bytes()),
+            // Known once the page is rendered or found unchanged.
+            hash: 0,
+        })
+    }
+
+    pub fn get_pages(&self) -> Vec<BlockRef> {
+        self.get_blocks()
+            .into_iter()
+            .filter(|block| block.borrow().is_page())
+            .collect()
     }
 
     pub fn get_blocks(&self) -> Vec<BlockRef> {
-        self.store
+        let store = self.store.borrow();
+        self.order
             .borrow()
-            .values()
-            .map(|block| Rc::clone(block))
+            .iter()
+            .filter_map(|id| store.get(id).map(Rc::clone))
             .collect()
     }
 
-    fn get_block(&self, block_id: &String) -> BlockRef {
-        Rc::clone(self.store.borrow().get(block_id).unwrap())
+    fn get_block(&self, block_id: &String) -> Result<BlockRef> {
+        self.store
+            .borrow()
+            .get(block_id)
+            .map(Rc::clone)
+            .ok_or_else(|| Error::node(block_id, NodeIssue::MissingNode))
     }
 
     fn add_block(&self, block: BlockRef) {
-        self.store
-            .borrow_mut()
-            .insert(block.borrow().id.clone(), Rc::clone(&block));
+        let id = block.borrow().id.clone();
+        self.order.borrow_mut().push(id.clone());
+        self.store.borrow_mut().insert(id, Rc::clone(&block));
     }
 
     fn contains_block(&self, block_id: &String) -> bool {
         self.store.borrow().contains_key(block_id)
     }
 
-    fn build_block(&self, node: NodeRef) {
-        let id = &node.borrow().id;
-        if self.contains_block(id) {
-            return;
+    fn build_block(&self, node: NodeRef) -> Result<()> {
+        let id = node.borrow().id.clone();
+        if self.contains_block(&id) || self.report.is_skipped(&id) {
+            return Ok(());
         }
 
-        let props = node.borrow().get_props();
+        match self.try_build_block(node) {
+            Err(Error::Node { issue, .. }) if self.node_builder.is_lenient() => {
+                self.store.borrow_mut().remove(&id);
+                self.report.skip(&id, Stage::BuildBlocks, issue);
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    fn try_build_block(&self, node: NodeRef) -> Result<()> {
+        let id = &node.borrow().id;
+        let props = node.borrow().get_props()?;
+        let tags = node.borrow().get_tag_list()?;
+        let todo = match props.done {
+            Some(done) => Some(done),
+            None if tags.contains(&String::from("todo")) => Some(false),
+            None => None,
+        };
 
         let block_ref = Rc::new(RefCell::new(Block {
             id: id.clone(),
             title: String::from(""),
+            inlines: vec![],
             description: props.description.clone(),
-            tags: node.borrow().get_tag_list(),
-            metadata: HashMap::new(),
+            tags,
+            metadata: vec![],
             children: RefCell::new(vec![]),
-            doc_type: node.borrow().get_doc_type(),
+            doc_type: node.borrow().get_doc_type()?,
+            language: node.borrow().get_code_language()?,
+            search: node.borrow().get_search_expression()?,
+            todo,
+            journal: None,
+            page: false,
+            folders: vec![],
+            source: None,
+            created: props.created,
+            modified: props.modified.unwrap_or(props.created),
             ref_count: 0,
         }));
 
-        self.add_block(block_ref);
+        self.add_block(Rc::clone(&block_ref));
 
-        self.build_block_title(Rc::clone(&node));
-        self.build_block_children(Rc::clone(&node));
+        if let Some(source) = &props.source_node {
+            self.build_block(Rc::clone(source))?;
+            let source_id = &source.borrow().id;
+            if self.contains_block(source_id) {
+                let source = self.get_block(source_id)?;
+                source.borrow_mut().ref_count += 1;
+                block_ref.borrow_mut().source = Some(source);
+            }
+        }
+
+        self.build_block_title(Rc::clone(&node))?;
+        self.build_block_journal(Rc::clone(&node))?;
+        self.build_block_children(Rc::clone(&node))
     }
 
-    fn build_block_by_id(&self, id: &String) {
-        if self.node_builder.contains_node(id) {
-            self.build_block(self.node_builder.get_node(id));
-        };
+    /// Recognizes calendar day nodes by their `day` supertag or by a name
+    /// like `2023-11-14 - Tuesday`.
+    fn build_block_journal(&self, node: NodeRef) -> Result<()> {
+        let block = self.get_block(&node.borrow().id)?;
+        let mut block = block.borrow_mut();
+
+        let is_day_tagged = block.tags.iter().any(|tag| tag == "day");
+        let is_day_titled = is_day_title(&block.title);
+
+        if is_day_tagged || is_day_titled {
+            block.journal = Date::parse(&block.title);
+        }
+
+        Ok(())
     }
 
-    fn build_block_children(&self, node: NodeRef) {
+    fn build_block_by_id(&self, id: &String) -> Result<()> {
+        self.build_block(self.node_builder.get_node(id)?)
+    }
+
+    fn build_block_children(&self, node: NodeRef) -> Result<()> {
         let id = &node.borrow().id;
+        let block = self.get_block(id)?;
 
-        node.borrow()
-            .get_children()
-            .iter()
-            .filter(|child| !child.borrow().is_in_trash() && !child.borrow().is_sys_node())
-            .for_each(|child| {
-                self.build_block(Rc::clone(child));
-                let doc_type = self.get_block(&child.borrow().id).borrow().doc_type.clone();
-
-                match doc_type {
-                    DocType::Text => {
-                        let child_block = self.get_block(&child.borrow().id);
-                        child_block.borrow_mut().ref_count += 1;
-                        self.get_block(id)
-                            .borrow_mut()
-                            .children
-                            .borrow_mut()
-                            .push(Rc::clone(&child_block));
+        for child in node.borrow().get_children() {
+            if child.borrow().is_in_trash()? || child.borrow().is_sys_node() {
+                continue;
+            }
+
+            self.build_block(Rc::clone(&child))?;
+            if !self.contains_block(&child.borrow().id) {
+                continue;
+            }
+
+            let mut child_block = self.get_block(&child.borrow().id)?;
+            let doc_type = child_block.borrow().doc_type.clone();
+
+            // A node listed under a parent other than its owner is shown there
+            // as a reference, not as a second copy. Field values and search
+            // results always live elsewhere, so they aren't references.
+            let owner = child.borrow().get_owner_node()?;
+            let is_mirrored = owner.is_some_and(|owner| &owner.borrow().id != id);
+            if is_mirrored && matches!(block.borrow().doc_type, DocType::Text) {
+                child_block.borrow_mut().ref_count += 1;
+                child_block =
+                    new_reference_block(format!("{}/{}", id, child.borrow().id), &child_block);
+            }
+
+            match doc_type {
+                DocType::Text | DocType::Codeblock | DocType::Search => {
+                    block
+                        .borrow()
+                        .children
+                        .borrow_mut()
+                        .push(Rc::clone(&child_block));
+                }
+                DocType::Tuple => {
+                    let children = child_block.borrow().get_children();
+                    if children.len() < 2 {
+                        continue;
                     }
-                    DocType::Tuple => {
-                        let child_block = self.get_block(&child.borrow().id);
-                        let children = child_block.borrow().get_children();
-                        if children.len() < 2 {
-                            return;
-                        }
-                        let key = children[0].borrow().title.clone();
-                        let values: Vec<BlockRef> =
-                            children[1..].iter().map(|val| Rc::clone(val)).collect();
-                        self.get_block(id).borrow_mut().metadata.insert(key, values);
+                    let key = children[0].borrow().title.clone();
+                    let values: Vec<BlockRef> = children[1..].iter().map(Rc::clone).collect();
+                    let mut block = block.borrow_mut();
+                    match block.metadata.iter_mut().find(|(field, _)| field == &key) {
+                        Some((_, existing)) => *existing = values,
+                        None => block.metadata.push((key, values)),
                     }
-                    _ => {}
                 }
-            });
+            }
+        }
+
+        Ok(())
     }
 
-    fn build_block_title(&self, node: NodeRef) {
+    fn build_block_title(&self, node: NodeRef) -> Result<()> {
         let id = &node.borrow().id;
-        let name = node.borrow().get_name();
-        if name.is_none() {
+        let name = match node.borrow().get_name()? {
+            Some(name) => name,
+            None => return Ok(()),
+        };
+
+        let mut inlines = vec![];
+        let mut last_end = 0;
+
+        for caps in INLINE_REF.captures_iter(&name) {
+            let span = caps.get(0).unwrap();
+            if span.start() > last_end {
+                inlines.push(Inline::Text(name[last_end..span.start()].to_string()));
+            }
+            last_end = span.end();
+
+            if &caps[1] == "date" {
+                inlines.push(parse_date_reference(&caps[2]));
+                continue;
+            }
+
+            let ref_id = caps[2].to_string();
+            if self.node_builder.contains_node(&ref_id) {
+                self.build_block_by_id(&ref_id)?;
+            }
+            if !self.contains_block(&ref_id) {
+                return Err(Error::node(
+                    id,
+                    NodeIssue::DanglingReference {
+                        field: "inline reference",
+                        target: ref_id,
+                    },
+                ));
+            }
+            let target = self.get_block(&ref_id)?;
+            target.borrow_mut().ref_count += 1;
+            inlines.push(Inline::Ref(target));
+        }
+
+        if last_end < name.len() {
+            inlines.push(Inline::Text(name[last_end..].to_string()));
+        }
+
+        let title = inlines
+            .iter()
+            .map(|inline| match inline {
+                Inline::Text(text) => text.clone(),
+                Inline::Ref(target) => target.borrow().title.clone(),
+                Inline::Date(date) => date.to_iso_string(),
+            })
+            .collect();
+
+        let block = self.get_block(id)?;
+        block.borrow_mut().title = title;
+        block.borrow_mut().inlines = inlines;
+
+        Ok(())
+    }
+}
+
+/// Whether `title` is a day node name like `2023-11-14` or
+/// `2023-11-14 - Tuesday`.
+fn is_day_title(title: &str) -> bool {
+    let is_date = title.len() >= 10
+        && title
+            .bytes()
+            .take(10)
+            .all(|b| b.is_ascii_digit() || b == b'-')
+        && Date::parse(title).is_some();
+    let is_weekday = |rest: &str| {
+        rest.strip_prefix(" - ")
+            .is_some_and(|day| !day.is_empty() && day.bytes().all(|b| b.is_ascii_alphabetic()))
+    };
+
+    is_date && (title.len() == 10 || is_weekday(&title[10..]))
+}
+
+/// A block standing for `source` where it is placed under another parent. It
+/// isn't part of the store, so it never becomes a page.
+fn new_reference_block(id: String, source: &BlockRef) -> BlockRef {
+    let original = source.borrow();
+    Rc::new(RefCell::new(Block {
+        id,
+        title: String::from(""),
+        inlines: vec![],
+        description: None,
+        tags: vec![],
+        metadata: vec![],
+        children: RefCell::new(vec![]),
+        doc_type: DocType::Text,
+        language: None,
+        search: None,
+        todo: None,
+        journal: None,
+        page: false,
+        folders: vec![],
+        source: Some(Rc::clone(source)),
+        created: original.created,
+        modified: original.modified,
+        ref_count: 0,
+    }))
+}
+
+fn collect_dependencies(
+    block: &BlockRef,
+    page_index: &PageIndex,
+    visited: &mut HashSet<String>,
+    modified: &mut u64,
+    links: &mut BTreeSet<PathBuf>,
+) {
+    let block = block.borrow();
+    let is_root = visited.is_empty();
+    if !visited.insert(block.id.clone()) {
+        return;
+    }
+
+    *modified = (*modified).max(block.modified);
+
+    // Other pages only show up as links, so their content doesn't matter.
+    if !is_root {
+        if let Some(path) = page_index.get_path(&block.id) {
+            links.insert(path.clone());
             return;
         }
+    }
+
+    // A referenced block is linked inside the page it is written on.
+    let source = block.source.as_ref().map(Rc::clone);
+    if let Some(home_path) = source
+        .as_ref()
+        .and_then(|source| page_index.get_home(&source.borrow().id))
+        .and_then(|home_id| page_index.get_path(home_id))
+    {
+        links.insert(home_path.clone());
+    }
+
+    // Dates link to their journal page, if it is exported.
+    block.inlines.iter().for_each(|inline| {
+        if let Some(path) = match inline {
+            Inline::Date(date) => page_index
+                .get_journal(date)
+                .and_then(|journal_id| page_index.get_path(journal_id)),
+            Inline::Text(_) | Inline::Ref(_) => None,
+        } {
+            links.insert(path.clone());
+        }
+    });
+
+    let inline_refs = block.inlines.iter().filter_map(|inline| match inline {
+        Inline::Ref(target) => Some(Rc::clone(target)),
+        Inline::Text(_) | Inline::Date(_) => None,
+    });
+    let field_values = block
+        .metadata
+        .iter()
+        .flat_map(|(_, values)| values.iter().map(Rc::clone));
+    let dependencies: Vec<BlockRef> = inline_refs
+        .chain(field_values)
+        .chain(source)
+        .chain(block.get_children())
+        .collect();
+
+    dependencies.iter().for_each(|dependency| {
+        collect_dependencies(dependency, page_index, visited, modified, links)
+    });
+}
+
+/// Reads a date reference, which Tana stores as HTML-escaped JSON such as
+/// `{&quot;dateTimeString&quot;:&quot;2023-11-14&quot;}`. References that
+/// can't be read are kept as their raw text.
+fn parse_date_reference(attribute: &str) -> Inline {
+    let json = attribute.replace("&quot;", "\"").replace("&amp;", "&");
+    let date_time = serde_json::from_str::<serde_json::Value>(&json)
+        .ok()
+        .and_then(|value| value.get("dateTimeString")?.as_str().map(String::from));
+
+    match date_time.as_deref().and_then(Date::parse) {
+        Some(date) => Inline::Date(date),
+        None => Inline::Text(date_time.unwrap_or(json)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use serde_json::{json, Value};
+
+    use crate::{
+        config::ExportOptions, export::Export, renderer::MarkdownRenderer, sink::RenderedPage,
+    };
+
+    use super::*;
+
+    /// Records which pages an incremental export touched.
+    #[derive(Default)]
+    struct ChangeSink {
+        written: Vec<String>,
+        removed: Vec<PathBuf>,
+    }
+
+    impl PageSink for ChangeSink {
+        fn write_page(&mut self, page: RenderedPage) -> Result<()> {
+            self.written.push(page.id);
+            Ok(())
+        }
 
-        let name = name.unwrap();
-        let re = Regex::new("(.*?)<span data-inlineref-node=(.*?)></span>").unwrap();
-        let title = re
-            .replace_all(&name, |caps: &regex::Captures| {
-                let id = &caps[2][1..&caps[2].len() - 1].to_string();
-                self.build_block_by_id(id);
-                let child = self.get_block(id);
-                format!("{}{}", &caps[1], child.borrow().get_link())
+        fn remove_page(&mut self, path: &Path) -> Result<()> {
+            self.removed.push(path.to_path_buf());
+            Ok(())
+        }
+    }
+
+    /// Alpha shows a block written on Beta and links to Beta and to a day,
+    /// which is kept out of the top level so it is only a journal page while
+    /// it has content. Gamma stands alone.
+    fn fixture() -> Value {
+        // The root is the one node without an owner.
+        let node = |id: &str, name: &str, owner: &str, children: &[&str]| {
+            let owner = Some(owner).filter(|owner| !owner.is_empty());
+            json!({
+                "id": id,
+                "props": { "created": 1700000000000u64, "name": name, "_ownerId": owner },
+                "children": children,
             })
-            .to_string();
+        };
+
+        let containers = ["WS_LIBRARY", "WS_SCHEMA", "WS_TRASH", "WS_CAPTURE_INBOX"];
+        let root_children = [&containers[..], &["a", "b", "c"]].concat();
+        let date = "{&quot;dateTimeString&quot;:&quot;2023-11-14&quot;}";
+        let date_ref = format!("On <span data-inlineref-date=\"{}\"></span>", date);
+
+        json!({
+            "formatVersion": 1,
+            "docs": [
+                node("WS", "Workspace", "", &root_children),
+                node("WS_LIBRARY", "Library", "WS", &[]),
+                node("WS_SCHEMA", "Schema", "WS", &[]),
+                node("WS_TRASH", "Trash", "WS", &[]),
+                node("WS_CAPTURE_INBOX", "Inbox", "WS", &["day"]),
+                node("a", "Alpha", "WS", &["a1", "a2", "b1"]),
+                node("a1", "See <span data-inlineref-node=\"b\"></span>", "a", &[]),
+                node("a2", &date_ref, "a", &[]),
+                node("b", "Beta", "WS", &["b1"]),
+                node("b1", "Shared", "b", &[]),
+                node("c", "Gamma", "WS", &[]),
+                node("day", "2023-11-14", "WS_CAPTURE_INBOX", &["d1"]),
+                node("d1", "Entry", "day", &[]),
+            ],
+        })
+    }
+
+    fn doc<'a>(fixture: &'a mut Value, id: &str) -> &'a mut Value {
+        fixture["docs"]
+            .as_array_mut()
+            .unwrap()
+            .iter_mut()
+            .find(|doc| doc["id"] == id)
+            .unwrap()
+    }
+
+    fn export(fixture: &Value, previous: &Manifest) -> (Manifest, ChangeSink) {
+        let export = Export::from_json(&fixture.to_string(), &ExportOptions::default()).unwrap();
+        let mut sink = ChangeSink::default();
+        let next = export
+            .render_changed_pages_to(
+                &MarkdownRenderer,
+                &Filter::default(),
+                Manifest::new("markdown", 0),
+                previous,
+                &mut sink,
+            )
+            .unwrap();
+        (next, sink)
+    }
+
+    #[test]
+    fn unchanged_pages_are_skipped() {
+        let (previous, sink) = export(&fixture(), &Manifest::default());
+        assert_eq!(sink.written.len(), 4);
+
+        let (_, sink) = export(&fixture(), &previous);
+        assert!(sink.written.is_empty());
+        assert!(sink.removed.is_empty());
+    }
+
+    #[test]
+    fn changed_embedded_child_renders_the_embedding_page() {
+        let (previous, _) = export(&fixture(), &Manifest::default());
+
+        let mut fixture = fixture();
+        doc(&mut fixture, "b1")["props"]["name"] = json!("Shared, edited");
+        doc(&mut fixture, "b1")["modifiedTs"] = json!([1700000001000u64]);
+        let (_, sink) = export(&fixture, &previous);
+
+        assert_eq!(sink.written, ["a", "b"]);
+    }
+
+    #[test]
+    fn renamed_reference_renders_the_referencing_page() {
+        let (previous, _) = export(&fixture(), &Manifest::default());
+
+        let mut fixture = fixture();
+        doc(&mut fixture, "b")["props"]["name"] = json!("Beta renamed");
+        let (next, sink) = export(&fixture, &previous);
+
+        assert_eq!(sink.written, ["a", "b"]);
+        assert_eq!(sink.removed, [PathBuf::from("Beta.md")]);
+        assert!(!previous.is_current("a", &next.pages["a"]));
+    }
+
+    #[test]
+    fn removed_page_is_deleted() {
+        let (previous, _) = export(&fixture(), &Manifest::default());
+
+        let mut fixture = fixture();
+        doc(&mut fixture, "WS")["children"] = json!([
+            "WS_LIBRARY",
+            "WS_SCHEMA",
+            "WS_TRASH",
+            "WS_CAPTURE_INBOX",
+            "a",
+            "b"
+        ]);
+        fixture["docs"]
+            .as_array_mut()
+            .unwrap()
+            .retain(|doc| doc["id"] != "c");
+        let (next, sink) = export(&fixture, &previous);
+
+        assert!(sink.written.is_empty());
+        assert_eq!(sink.removed, [PathBuf::from("Gamma.md")]);
+        assert!(!next.pages.contains_key("c"));
+    }
+
+    #[test]
+    fn emptied_journal_renders_the_pages_linking_to_its_date() {
+        let (previous, _) = export(&fixture(), &Manifest::default());
+
+        let mut fixture = fixture();
+        doc(&mut fixture, "day")["children"] = json!([]);
+        fixture["docs"]
+            .as_array_mut()
+            .unwrap()
+            .retain(|doc| doc["id"] != "d1");
+        let (_, sink) = export(&fixture, &previous);
 
-        self.get_block(id).borrow_mut().title = title;
+        assert_eq!(sink.written, ["a"]);
+        assert_eq!(sink.removed.len(), 1);
     }
 }
@@ -1,48 +1,517 @@
 use std::fs;
+use std::io::Read;
 use std::path::Path;
 
+use crate::{
+    block::Block,
+    error::{Error, Result},
+    manifest::{content_hash, Manifest},
+    node_builder::InputFormat,
+    renderer,
+};
+
+pub const USAGE: &str = "Convert a Tana JSON export into Markdown pages.
+
+Usage:
+  tana-to-markdown export [OPTIONS] <INPUT> <OUTPUT>
+  tana-to-markdown --help
+  tana-to-markdown --version
+
+Arguments:
+  <INPUT>   Tana export file (.json, .zip or .json.gz), or - to read from stdin
+  <OUTPUT>  Directory the pages are written to, or - to write a single
+            stream to stdout (markdown format only)
+
+Options:
+  -f, --format <FORMAT>  Output format: logseq, obsidian, markdown [default: logseq]
+      --overwrite        Write into a non-empty output dir, replacing existing files
+      --merge            Write into a non-empty output dir, keeping existing files
+      --incremental      Only rewrite pages that changed since the last export into
+                         the output dir, and remove pages that are gone
+      --sync <STRATEGY>  Incremental export that detects files edited by hand since
+                         the last export and skips them, writes a .conflict copy next
+                         to them, or merges both versions: skip, conflict, merge
+      --include <TAG>    Only export pages tagged with TAG (repeatable)
+      --exclude <TAG>    Skip pages tagged with TAG (repeatable)
+      --page-tag <TAG>   Make blocks tagged with TAG pages (repeatable)
+      --page-depth <N>   Make blocks at most N levels below the workspace root pages
+      --page-id <ID>     Make the node with ID a page (repeatable)
+      --no-page-tag <TAG>
+                         Never make blocks tagged with TAG pages (repeatable)
+      --page-min-children <N>
+                         Only make blocks with at least N children pages
+      --no-top-level-pages
+                         Don't make every node in the workspace root or library a
+                         page, the library's ones go into a folder of their own
+      --nested           Place pages in folders mirroring where they live in Tana,
+                         as namespaces (a/b/c) for Logseq
+      --daily-notes-folder <DIR>
+                         Folder of Obsidian journal pages [default: Daily Notes]
+      --daily-notes-format <FORMAT>
+                         Moment.js date format of Obsidian journal page names
+                         [default: YYYY-MM-DD]
+      --block-ids        Give every block referenced from elsewhere an id and point
+                         the references at it (logseq and obsidian)
+      --backlinks        End every page with a section listing the pages that
+                         reference it
+      --backlinks-title <TITLE>
+                         Title of the backlinks section, implies --backlinks
+                         [default: Linked references]
+      --lenient          Skip broken nodes and list them in a report instead of aborting
+  -n, --dry-run          Show which pages would be written without writing anything
+  -v, --verbose          Print every file that is written
+  -q, --quiet            Only print errors
+  -h, --help             Print help
+  -V, --version          Print version
+
+Without --page-tag or --page-depth, blocks with a tag or field become pages, in
+addition to those picked by --page-id and the top-level rule.";
+
+/// Stands for stdin as input and stdout as output.
+pub const STDIO: &str = "-";
+
+pub enum Command {
+    Export(Box<Config>),
+    Help,
+    Version,
+}
+
+/// How to treat an output directory that already contains files.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WriteMode {
+    /// Refuse to write into a non-empty directory.
+    Empty,
+    /// Replace files that already exist.
+    Overwrite,
+    /// Keep files that already exist and only add new ones.
+    Merge,
+}
+
+/// What a sync does with a page whose file was edited since the last export.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SyncStrategy {
+    /// Leave the edited file alone.
+    Skip,
+    /// Keep the edited file and write the export next to it.
+    Conflict,
+    /// Three-way merge the edited file with the export.
+    Merge,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+/// Selects pages by their supertags.
+#[derive(Default)]
+pub struct Filter {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+/// Decides which blocks are written as pages of their own rather than as
+/// bullets of the page they are on.
+#[derive(Clone, Debug)]
+pub struct PageRules {
+    /// Blocks that are always pages.
+    pub ids: Vec<String>,
+    /// Blocks with one of these tags are pages.
+    pub tags: Vec<String>,
+    /// Blocks with one of these tags are never pages, unless listed in `ids`.
+    pub exclude_tags: Vec<String>,
+    /// Blocks at most this many levels below the workspace root are pages.
+    pub max_depth: Option<usize>,
+    /// Blocks with fewer children are never pages, unless listed in `ids`.
+    pub min_children: Option<usize>,
+    /// Direct children of the workspace root and the library are pages.
+    pub top_level: bool,
+    /// Place every page in folders mirroring the nodes it lives under, not
+    /// only the pages of the library.
+    pub nested: bool,
+}
+
+impl Default for PageRules {
+    fn default() -> Self {
+        PageRules {
+            ids: vec![],
+            tags: vec![],
+            exclude_tags: vec![],
+            max_depth: None,
+            min_children: None,
+            top_level: true,
+            nested: false,
+        }
+    }
+}
+
+/// How references between blocks are written, beyond links to whole pages.
+#[derive(Clone, Debug, Default)]
+pub struct LinkOptions {
+    /// Give blocks referenced from elsewhere an id, and point inline
+    /// references at it. Blocks embedded by reference nodes always get one.
+    pub block_ids: bool,
+    /// Title of the section listing the pages that reference a page, or
+    /// `None` to leave the section out.
+    pub backlinks: Option<String>,
+}
+
+/// How an `Export` is built from the data it is loaded from.
+#[derive(Clone, Debug, Default)]
+pub struct ExportOptions {
+    /// Skip nodes that can't be built and report them, instead of failing.
+    pub lenient: bool,
+    pub page_rules: PageRules,
+    pub links: LinkOptions,
+}
+
+/// Where Obsidian finds daily notes, mirroring its Daily notes settings.
+#[derive(Debug)]
+pub struct DailyNotes {
+    pub folder: String,
+    pub format: String,
+}
+
+impl Default for DailyNotes {
+    fn default() -> Self {
+        DailyNotes {
+            folder: String::from("Daily Notes"),
+            format: String::from("YYYY-MM-DD"),
+        }
+    }
+}
+
 pub struct Config {
     pub input: String,
     pub output: String,
+    pub format: String,
+    pub write_mode: WriteMode,
+    /// Track written pages in a manifest and only rewrite changed ones.
+    pub incremental: bool,
+    /// Protect files edited by hand, implies `incremental`.
+    pub sync: Option<SyncStrategy>,
+    pub filter: Filter,
+    pub page_rules: PageRules,
+    pub daily_notes: DailyNotes,
+    pub links: LinkOptions,
+    pub lenient: bool,
+    pub dry_run: bool,
+    pub verbosity: Verbosity,
+}
+
+impl Command {
+    pub fn parse(args: &[String]) -> Result<Command> {
+        let mut args = args.iter().skip(1).peekable();
+
+        match args.peek().map(|arg| arg.as_str()) {
+            None | Some("-h") | Some("--help") | Some("help") => return Ok(Command::Help),
+            Some("-V") | Some("--version") => return Ok(Command::Version),
+            Some("export") => {
+                args.next();
+            }
+            // Without a subcommand the arguments are read as an export, which
+            // keeps `tana-to-markdown <input> <output>` working.
+            Some(_) => {}
+        }
+
+        let args: Vec<String> = args.cloned().collect();
+        if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+            return Ok(Command::Help);
+        }
+
+        Ok(Command::Export(Box::new(Config::new(&args)?)))
+    }
 }
 
 impl Config {
-    pub fn new(args: &[String]) -> Result<Config, &'static str> {
-        if args.len() < 3 {
-            return Err("Missing arguments");
+    pub fn new(args: &[String]) -> Result<Config> {
+        let mut positional = vec![];
+        let mut format = String::from("logseq");
+        let mut write_mode = WriteMode::Empty;
+        let mut incremental = false;
+        let mut sync = None;
+        let mut filter = Filter::default();
+        let mut page_rules = PageRules::default();
+        let mut daily_notes = DailyNotes::default();
+        let mut links = LinkOptions::default();
+        let mut lenient = false;
+        let mut dry_run = false;
+        let mut verbosity = Verbosity::Normal;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) if arg.starts_with("--") => (flag, Some(value.to_string())),
+                _ => (arg.as_str(), None),
+            };
+            let mut value = || {
+                inline_value
+                    .clone()
+                    .or_else(|| args.next().cloned())
+                    .ok_or_else(|| Error::Config(format!("Missing value for '{}'", flag)))
+            };
+
+            match flag {
+                "-f" | "--format" => format = value()?,
+                "--include" => filter.include.push(value()?),
+                "--exclude" => filter.exclude.push(value()?),
+                "--page-tag" => page_rules.tags.push(value()?),
+                "--no-page-tag" => page_rules.exclude_tags.push(value()?),
+                "--page-id" => page_rules.ids.push(value()?),
+                "--no-top-level-pages" => page_rules.top_level = false,
+                "--nested" => page_rules.nested = true,
+                "--page-depth" => page_rules.max_depth = Some(parse_count(flag, &value()?)?),
+                "--page-min-children" => {
+                    page_rules.min_children = Some(parse_count(flag, &value()?)?)
+                }
+                "--daily-notes-folder" => daily_notes.folder = value()?,
+                "--daily-notes-format" => daily_notes.format = value()?,
+                "--overwrite" => write_mode = WriteMode::Overwrite,
+                "--merge" => write_mode = WriteMode::Merge,
+                "--incremental" => incremental = true,
+                "--sync" => {
+                    sync = Some(SyncStrategy::parse(&value()?)?);
+                    incremental = true;
+                }
+                "--block-ids" => links.block_ids = true,
+                "--backlinks" => {
+                    links
+                        .backlinks
+                        .get_or_insert_with(|| String::from("Linked references"));
+                }
+                "--backlinks-title" => links.backlinks = Some(value()?),
+                "--lenient" => lenient = true,
+                "-n" | "--dry-run" => dry_run = true,
+                "-v" | "--verbose" => verbosity = Verbosity::Verbose,
+                "-q" | "--quiet" => verbosity = Verbosity::Quiet,
+                flag if flag.starts_with('-') && flag.len() > 1 => {
+                    return Err(Error::Config(format!(
+                        "Unknown option '{}'\n\n{}",
+                        flag, USAGE
+                    )));
+                }
+                _ => positional.push(arg.clone()),
+            }
+        }
+
+        if positional.len() != 2 {
+            return Err(Error::Config(format!(
+                "Expected an input file and an output dir\n\n{}",
+                USAGE
+            )));
         }
 
-        let input = args[1].clone();
-        Config::check_input(&input)?;
+        let output = positional.pop().unwrap_or_default();
+        let input = positional.pop().unwrap_or_default();
 
-        let output = args[2].clone();
-        Config::check_output(&output)?;
+        if input != STDIO {
+            Config::check_input(&input)?;
+        }
+        Config::check_format(&format)?;
+        if incremental && write_mode == WriteMode::Merge {
+            return Err(Error::Config(String::from(
+                "--incremental and --sync rewrite changed pages and can't be combined with --merge",
+            )));
+        }
+        if output == STDIO {
+            if incremental {
+                return Err(Error::Config(String::from(
+                    "--incremental and --sync need an output dir to keep their manifest in",
+                )));
+            }
+            Config::check_stream_output(&format, dry_run)?;
+        } else {
+            Config::check_output(&output, write_mode, incremental)?;
+        }
 
-        Ok(Config { input, output })
+        Ok(Config {
+            input,
+            output,
+            format,
+            write_mode,
+            incremental,
+            sync,
+            filter,
+            page_rules,
+            daily_notes,
+            links,
+            lenient,
+            dry_run,
+            verbosity,
+        })
     }
 
-    fn check_input(input: &String) -> Result<bool, &'static str> {
+    pub fn get_export_options(&self) -> ExportOptions {
+        ExportOptions {
+            lenient: self.lenient,
+            page_rules: self.page_rules.clone(),
+            links: self.links.clone(),
+        }
+    }
+
+    /// Hash of the settings other than the format that change how pages are
+    /// rendered, so an incremental export notices when they did.
+    pub fn get_options_hash(&self) -> u64 {
+        let options = format!(
+            "{:?}\n{:?}\n{:?}",
+            self.page_rules, self.daily_notes, self.links
+        );
+        content_hash(options.as_bytes())
+    }
+
+    fn check_input(input: &String) -> Result<bool> {
         let path = Path::new(input);
-        let metadata = fs::metadata(path).expect("File not found");
+        let metadata = fs::metadata(path).map_err(|err| Error::io(path, err))?;
 
-        if !metadata.is_file() || !input.ends_with(".json") {
-            return Err("Input file must be '.json'");
+        if !metadata.is_file() {
+            return Err(Error::Config(String::from("Input must be a file")));
         }
 
-        fs::OpenOptions::new()
-            .read(true)
-            .open(&input)
-            .expect("Permission denied");
+        // Archives are recognized by their content, so only plain JSON has to
+        // look like an object here.
+        let mut head = [0; 512];
+        let len = fs::File::open(path)
+            .and_then(|mut file| file.read(&mut head))
+            .map_err(|err| Error::io(path, err))?;
+        let is_json = head[..len].iter().find(|byte| !byte.is_ascii_whitespace()) == Some(&b'{');
+
+        if InputFormat::detect(&head[..len]) == InputFormat::Json && !is_json {
+            return Err(Error::Config(String::from(
+                "Input must be a Tana JSON export, or a .zip or .json.gz containing one",
+            )));
+        }
 
         Ok(true)
     }
 
-    fn check_output(output: &String) -> Result<bool, &'static str> {
+    fn check_output(output: &String, write_mode: WriteMode, incremental: bool) -> Result<bool> {
         let path = Path::new(&output);
-        if path.extension().is_some() || path.exists() && fs::read_dir(path).unwrap().count() > 0 {
-            return Err("output must be a emput dir");
+        if path.exists() && !path.is_dir() || !path.exists() && path.extension().is_some() {
+            return Err(Error::Config(String::from("Output must be a dir")));
+        }
+
+        let is_empty = match path.exists() {
+            true => {
+                fs::read_dir(path)
+                    .map_err(|err| Error::io(path, err))?
+                    .count()
+                    == 0
+            }
+            false => true,
+        };
+
+        // An earlier incremental export may write into its own output again.
+        let has_manifest = incremental && Manifest::exists(path);
+
+        if write_mode == WriteMode::Empty && !is_empty && !has_manifest {
+            return Err(Error::Config(String::from(
+                "Output must be an empty dir, pass --overwrite or --merge to write into it anyway",
+            )));
+        }
+
+        Ok(true)
+    }
+
+    fn check_stream_output(format: &str, dry_run: bool) -> Result<bool> {
+        let supports_stream = renderer::get_renderer(format)
+            .map(|renderer| renderer.supports_single_stream())
+            .unwrap_or(false);
+
+        if !supports_stream {
+            return Err(Error::Config(format!(
+                "Format '{}' writes one file per page and can't be written to stdout",
+                format
+            )));
+        }
+
+        if dry_run {
+            return Err(Error::Config(String::from(
+                "--dry-run has nothing to preview when writing to stdout",
+            )));
         }
 
         Ok(true)
     }
+
+    pub fn is_stdout(&self) -> bool {
+        self.output == STDIO
+    }
+
+    fn check_format(format: &str) -> Result<bool> {
+        if !renderer::FORMATS.contains(&format) {
+            return Err(Error::Config(format!(
+                "Format must be one of {}",
+                renderer::FORMATS.join(", ")
+            )));
+        }
+
+        Ok(true)
+    }
+}
+
+impl SyncStrategy {
+    pub fn parse(strategy: &str) -> Result<SyncStrategy> {
+        match strategy {
+            "skip" => Ok(SyncStrategy::Skip),
+            "conflict" => Ok(SyncStrategy::Conflict),
+            "merge" => Ok(SyncStrategy::Merge),
+            _ => Err(Error::Config(format!(
+                "Sync strategy must be one of skip, conflict, merge, got '{}'",
+                strategy
+            ))),
+        }
+    }
+}
+
+impl PageRules {
+    /// Whether `block`, lying `depth` levels below the workspace root, is a
+    /// page. `is_top_level` tells whether it sits directly in the root or the
+    /// library. Without tag or depth rules the built-in rule of
+    /// `Block::looks_like_page` applies.
+    pub fn matches(&self, block: &Block, depth: usize, is_top_level: bool) -> bool {
+        if self.ids.contains(&block.id) {
+            return true;
+        }
+
+        let is_excluded = block.tags.iter().any(|tag| self.exclude_tags.contains(tag));
+        let has_children = self
+            .min_children
+            .is_none_or(|min_children| block.children.borrow().len() >= min_children);
+        if !block.can_be_page() || is_excluded || !has_children {
+            return false;
+        }
+
+        if block.is_journal_page() || self.top_level && is_top_level {
+            return true;
+        }
+
+        if self.tags.is_empty() && self.max_depth.is_none() {
+            return block.looks_like_page();
+        }
+
+        let has_page_tag = block.tags.iter().any(|tag| self.tags.contains(tag));
+        // The workspace root itself is the one block at depth 0.
+        let is_shallow = self
+            .max_depth
+            .is_some_and(|max_depth| depth > 0 && depth <= max_depth);
+        has_page_tag || is_shallow
+    }
+}
+
+impl Filter {
+    pub fn matches(&self, block: &Block) -> bool {
+        let included =
+            self.include.is_empty() || block.tags.iter().any(|tag| self.include.contains(tag));
+        let excluded = block.tags.iter().any(|tag| self.exclude.contains(tag));
+
+        included && !excluded
+    }
+}
+
+fn parse_count(flag: &str, value: &str) -> Result<usize> {
+    value
+        .parse()
+        .map_err(|_| Error::Config(format!("'{}' expects a number, got '{}'", flag, value)))
 }
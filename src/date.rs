@@ -0,0 +1,234 @@
+const MONTH_NAMES: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+const WEEKDAY_NAMES: [&str; 7] = [
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+];
+
+/// Tokens understood by `Date::format`, longest first so `MMMM` wins over `MM`.
+const FORMAT_TOKENS: [&str; 11] = [
+    "YYYY", "YY", "MMMM", "MMM", "MM", "M", "Do", "DD", "D", "dddd", "ddd",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Date {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+}
+
+impl Date {
+    pub fn from_timestamp(timestamp: u64) -> Date {
+        // Tana stores timestamps in milliseconds since the Unix epoch (UTC).
+        Date::from_days((timestamp / 1000 / 86400) as i64)
+    }
+
+    fn from_days(days: i64) -> Date {
+        // Civil-from-days, see http://howardhinnant.github.io/date_algorithms.html
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z.rem_euclid(146097);
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+        Date { year, month, day }
+    }
+
+    /// Days since the Unix epoch, the inverse of `from_days`.
+    fn to_days(self) -> i64 {
+        let year = if self.month <= 2 {
+            self.year - 1
+        } else {
+            self.year
+        };
+        let era = year.div_euclid(400);
+        let yoe = year.rem_euclid(400);
+        let mp = (self.month as i64 + 9) % 12;
+        let doy = (153 * mp + 2) / 5 + self.day as i64 - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        era * 146097 + doe - 719468
+    }
+
+    /// Reads the `YYYY-MM-DD` date at the start of `text`, as Tana writes
+    /// both day node names and date references.
+    pub fn parse(text: &str) -> Option<Date> {
+        let text = text.get(..10)?;
+        let bytes = text.as_bytes();
+        if bytes[4] != b'-' || bytes[7] != b'-' {
+            return None;
+        }
+
+        let date = Date {
+            year: text[..4].parse().ok()?,
+            month: text[5..7].parse().ok()?,
+            day: text[8..10].parse().ok()?,
+        };
+
+        // Out of range parts don't survive the round trip.
+        match Date::from_days(date.to_days()) == date && (1..=12).contains(&date.month) {
+            true => Some(date),
+            false => None,
+        }
+    }
+
+    /// Day of the week, from 0 for Monday to 6 for Sunday.
+    pub fn weekday(&self) -> usize {
+        // The epoch was a Thursday.
+        (self.to_days() + 3).rem_euclid(7) as usize
+    }
+
+    pub fn to_iso_string(&self) -> String {
+        format!("{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+
+    /// Formats the date with Moment.js tokens (`YYYY`, `YY`, `MMMM`, `MMM`,
+    /// `MM`, `M`, `DD`, `Do`, `D`, `dddd`, `ddd`), the syntax Obsidian uses for
+    /// daily note names. Text in square brackets is kept as is.
+    pub fn format(&self, pattern: &str) -> String {
+        let mut formatted = String::new();
+        let mut rest = pattern;
+
+        while let Some(c) = rest.chars().next() {
+            if c == '[' {
+                let end = rest.find(']').unwrap_or(rest.len());
+                formatted.push_str(&rest[1..end]);
+                rest = rest.get(end + 1..).unwrap_or("");
+                continue;
+            }
+
+            match FORMAT_TOKENS.iter().find(|token| rest.starts_with(*token)) {
+                Some(token) => {
+                    formatted.push_str(&self.format_token(token));
+                    rest = &rest[token.len()..];
+                }
+                None => {
+                    formatted.push(c);
+                    rest = &rest[c.len_utf8()..];
+                }
+            }
+        }
+
+        formatted
+    }
+
+    fn format_token(&self, token: &str) -> String {
+        let month_name = MONTH_NAMES[self.month as usize - 1];
+        let weekday_name = WEEKDAY_NAMES[self.weekday()];
+
+        match token {
+            "YYYY" => format!("{:04}", self.year),
+            "YY" => format!("{:02}", self.year.rem_euclid(100)),
+            "MMMM" => month_name.to_string(),
+            "MMM" => month_name[..3].to_string(),
+            "MM" => format!("{:02}", self.month),
+            "M" => self.month.to_string(),
+            "Do" => format!("{}{}", self.day, ordinal_suffix(self.day)),
+            "DD" => format!("{:02}", self.day),
+            "D" => self.day.to_string(),
+            "dddd" => weekday_name.to_string(),
+            "ddd" => weekday_name[..3].to_string(),
+            _ => token.to_string(),
+        }
+    }
+}
+
+fn ordinal_suffix(day: u32) -> &'static str {
+    match (day % 10, day % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i64, month: u32, day: u32) -> Date {
+        Date { year, month, day }
+    }
+
+    #[test]
+    fn parse_reads_leading_iso_date() {
+        assert_eq!(Date::parse("2023-11-14"), Some(date(2023, 11, 14)));
+        assert_eq!(
+            Date::parse("2023-11-14 - Tuesday"),
+            Some(date(2023, 11, 14))
+        );
+        assert_eq!(Date::parse("2024-02-29"), Some(date(2024, 2, 29)));
+    }
+
+    #[test]
+    fn parse_rejects_invalid_dates() {
+        assert_eq!(Date::parse("2023-02-29"), None);
+        assert_eq!(Date::parse("2023-13-01"), None);
+        assert_eq!(Date::parse("2023-00-10"), None);
+        assert_eq!(Date::parse("2023-11-00"), None);
+        assert_eq!(Date::parse("2023/11/14"), None);
+        assert_eq!(Date::parse("2023-1-14"), None);
+        assert_eq!(Date::parse("2023-11-1é"), None);
+        assert_eq!(Date::parse(""), None);
+    }
+
+    #[test]
+    fn timestamps_are_read_as_utc_days() {
+        assert_eq!(Date::from_timestamp(1_700_000_000_000), date(2023, 11, 14));
+        assert_eq!(Date::from_timestamp(0), date(1970, 1, 1));
+        assert_eq!(date(2023, 11, 14).weekday(), 1);
+    }
+
+    #[test]
+    fn format_replaces_moment_tokens() {
+        let day = date(2023, 11, 14);
+
+        assert_eq!(day.format("YYYY-MM-DD"), "2023-11-14");
+        assert_eq!(day.format("MMM Do, YYYY"), "Nov 14th, 2023");
+        assert_eq!(day.format("dddd, MMMM D YY"), "Tuesday, November 14 23");
+        assert_eq!(date(2024, 3, 5).format("ddd D/M"), "Tue 5/3");
+        assert_eq!(day.format("YYYY/MM/YYYY-MM-DD"), "2023/11/2023-11-14");
+    }
+
+    #[test]
+    fn format_keeps_bracketed_text() {
+        let day = date(2023, 11, 14);
+
+        assert_eq!(day.format("[Week of] D MMM"), "Week of 14 Nov");
+        assert_eq!(day.format("YYYY [DD]"), "2023 DD");
+        assert_eq!(day.format("YYYY [open"), "2023 open");
+    }
+
+    #[test]
+    fn format_uses_english_ordinals() {
+        let ordinals =
+            [1, 2, 3, 4, 11, 12, 13, 21, 22, 23, 31].map(|day| date(2023, 1, day).format("Do"));
+
+        assert_eq!(
+            ordinals,
+            ["1st", "2nd", "3rd", "4th", "11th", "12th", "13th", "21st", "22nd", "23rd", "31st"]
+        );
+    }
+}
@@ -0,0 +1,130 @@
+use std::{fmt, io, path::PathBuf};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    Config(String),
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Json {
+        path: Option<PathBuf>,
+        source: serde_json::Error,
+    },
+    /// The export declares a `formatVersion` this build has no adapter for.
+    UnsupportedFormatVersion {
+        version: u64,
+        supported: &'static [u64],
+    },
+    Node {
+        id: String,
+        issue: NodeIssue,
+    },
+    Stage {
+        stage: Stage,
+        source: Box<Error>,
+    },
+}
+
+/// What is wrong with a single node of the export.
+#[derive(Clone, Debug)]
+pub enum NodeIssue {
+    MissingProps,
+    MissingNode,
+    MalformedTuple,
+    DanglingReference { field: &'static str, target: String },
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Stage {
+    LoadNodes,
+    BuildNodes,
+    BuildBlocks,
+    WritePages,
+}
+
+impl Error {
+    pub fn node(id: &str, issue: NodeIssue) -> Error {
+        Error::Node {
+            id: id.to_string(),
+            issue,
+        }
+    }
+
+    pub fn io(path: impl Into<PathBuf>, source: io::Error) -> Error {
+        Error::Io {
+            path: path.into(),
+            source,
+        }
+    }
+
+    pub fn in_stage(self, stage: Stage) -> Error {
+        Error::Stage {
+            stage,
+            source: Box::new(self),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Config(message) => write!(f, "{}", message),
+            Error::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::Json {
+                path: Some(path),
+                source,
+            } => write!(f, "{}: invalid Tana export: {}", path.display(), source),
+            Error::Json { path: None, source } => write!(f, "invalid Tana export: {}", source),
+            Error::UnsupportedFormatVersion { version, supported } => write!(
+                f,
+                "unsupported Tana export formatVersion {}, supported versions: {}",
+                version,
+                supported
+                    .iter()
+                    .map(|version| version.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            Error::Node { id, issue } => write!(f, "node {}: {}", id, issue),
+            Error::Stage { stage, source } => write!(f, "{} failed: {}", stage, source),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Json { source, .. } => Some(source),
+            Error::Stage { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for NodeIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NodeIssue::MissingProps => write!(f, "node has no props"),
+            NodeIssue::MissingNode => write!(f, "node does not exist in the export"),
+            NodeIssue::MalformedTuple => write!(f, "tuple is missing its key or values"),
+            NodeIssue::DanglingReference { field, target } => {
+                write!(f, "{} points to missing node {}", field, target)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Stage::LoadNodes => write!(f, "loading export"),
+            Stage::BuildNodes => write!(f, "building nodes"),
+            Stage::BuildBlocks => write!(f, "building blocks"),
+            Stage::WritePages => write!(f, "writing pages"),
+        }
+    }
+}
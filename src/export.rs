@@ -0,0 +1,201 @@
+use std::{
+    io::{self, Read},
+    path::Path,
+    rc::Rc,
+};
+
+use crate::{
+    block::BlockRef,
+    block_builder::BlockBuilder,
+    config::{Config, ExportOptions, Filter, STDIO},
+    error::{Result, Stage},
+    manifest::Manifest,
+    node::NodeRef,
+    node_builder::NodeBuilder,
+    renderer::Renderer,
+    report::Report,
+    sink::{DirSink, PageSink, RenderedPage, StreamSink},
+    sync::{SyncOutcome, SyncSink},
+};
+
+/// A fully built Tana export: the raw node graph plus the blocks derived
+/// from it, ready to be rendered.
+pub struct Export {
+    node_builder: Rc<NodeBuilder>,
+    block_builder: BlockBuilder,
+}
+
+/// What `Export::write` did besides writing pages.
+pub struct WriteSummary {
+    /// Files edited since the last export, which only a sync export checks.
+    pub edited: usize,
+}
+
+impl Export {
+    /// Builds nodes and blocks from a builder that already loaded its data.
+    fn new(mut node_builder: NodeBuilder, options: &ExportOptions) -> Result<Export> {
+        node_builder
+            .build_nodes()
+            .map_err(|err| err.in_stage(Stage::BuildNodes))?;
+
+        let node_builder = Rc::new(node_builder);
+        let mut block_builder = BlockBuilder::new(Rc::clone(&node_builder));
+        block_builder.set_link_options(options.links.clone());
+        block_builder
+            .build_blocks()
+            .and_then(|_| block_builder.select_pages(&options.page_rules))
+            .map_err(|err| err.in_stage(Stage::BuildBlocks))?;
+
+        Ok(Export {
+            node_builder,
+            block_builder,
+        })
+    }
+
+    fn load(
+        options: &ExportOptions,
+        load: impl FnOnce(&mut NodeBuilder) -> Result<()>,
+    ) -> Result<Export> {
+        let mut node_builder = NodeBuilder::new();
+        node_builder.set_lenient(options.lenient);
+        load(&mut node_builder).map_err(|err| err.in_stage(Stage::LoadNodes))?;
+        Export::new(node_builder, options)
+    }
+
+    pub fn from_path(path: impl AsRef<Path>, options: &ExportOptions) -> Result<Export> {
+        let path = path.as_ref().to_string_lossy().to_string();
+        Export::load(options, |node_builder| node_builder.load_data_source(path))
+    }
+
+    pub fn from_reader(reader: impl Read, options: &ExportOptions) -> Result<Export> {
+        Export::load(options, |node_builder| node_builder.load_reader(reader))
+    }
+
+    pub fn from_json(input: &str, options: &ExportOptions) -> Result<Export> {
+        Export::load(options, |node_builder| node_builder.load_str(input))
+    }
+
+    /// Loads the input of `config`, which may be stdin.
+    pub fn from_config(config: &Config) -> Result<Export> {
+        let options = config.get_export_options();
+        match config.input.as_str() {
+            STDIO => Export::from_reader(io::stdin().lock(), &options),
+            input => Export::from_path(input, &options),
+        }
+    }
+
+    pub fn get_nodes(&self) -> Vec<NodeRef> {
+        self.node_builder.get_nodes()
+    }
+
+    pub fn get_blocks(&self) -> Vec<BlockRef> {
+        self.block_builder.get_blocks()
+    }
+
+    pub fn get_pages(&self) -> Vec<BlockRef> {
+        self.block_builder.get_pages()
+    }
+
+    /// Nodes skipped while building a lenient export.
+    pub fn get_report(&self) -> Rc<Report> {
+        self.node_builder.get_report()
+    }
+
+    pub fn render_pages(&self, renderer: &dyn Renderer) -> Result<Vec<RenderedPage>> {
+        let mut pages = vec![];
+        self.render_pages_to(renderer, &Filter::default(), &mut pages)?;
+        Ok(pages)
+    }
+
+    pub fn render_pages_to(
+        &self,
+        renderer: &dyn Renderer,
+        filter: &Filter,
+        sink: &mut dyn PageSink,
+    ) -> Result<()> {
+        self.block_builder
+            .render_pages(renderer, filter, sink)
+            .map_err(|err| err.in_stage(Stage::WritePages))
+    }
+
+    /// Like `render_pages_to`, but skips pages unchanged since `previous`
+    /// and removes the ones that are gone. See `BlockBuilder::render_changed_pages`.
+    pub(crate) fn render_changed_pages_to(
+        &self,
+        renderer: &dyn Renderer,
+        filter: &Filter,
+        next: Manifest,
+        previous: &Manifest,
+        sink: &mut dyn PageSink,
+    ) -> Result<Manifest> {
+        self.block_builder
+            .render_changed_pages(renderer, filter, next, previous, sink)
+            .map_err(|err| err.in_stage(Stage::WritePages))
+    }
+
+    /// Writes the pages where `config` says: to stdout, or into the output
+    /// directory, only rewriting changed pages when incremental and leaving
+    /// edited files alone when syncing. The report of skipped nodes goes
+    /// next to the pages.
+    pub fn write(&self, config: &Config, renderer: &dyn Renderer) -> Result<WriteSummary> {
+        let mut summary = WriteSummary { edited: 0 };
+
+        if config.is_stdout() {
+            let mut sink = StreamSink::new(io::stdout().lock());
+            self.render_pages_to(renderer, &config.filter, &mut sink)?;
+            return Ok(summary);
+        }
+
+        let output = Path::new(&config.output);
+        if let Some(strategy) = config.sync {
+            let previous = Manifest::load(output).map_err(|err| err.in_stage(Stage::WritePages))?;
+            let mut sink = SyncSink::new(config, strategy, &previous)
+                .map_err(|err| err.in_stage(Stage::WritePages))?;
+            self.write_changed_pages(config, renderer, &previous, &mut sink)?;
+
+            summary.edited = sink
+                .get_outcomes()
+                .iter()
+                .filter(|(_, outcome)| {
+                    !matches!(outcome, SyncOutcome::Written | SyncOutcome::Removed)
+                })
+                .count();
+        } else if config.incremental {
+            let previous = Manifest::load(output).map_err(|err| err.in_stage(Stage::WritePages))?;
+            let mut sink = DirSink::new(config).map_err(|err| err.in_stage(Stage::WritePages))?;
+            self.write_changed_pages(config, renderer, &previous, &mut sink)?;
+        } else {
+            let mut sink = DirSink::new(config).map_err(|err| err.in_stage(Stage::WritePages))?;
+            self.render_pages_to(renderer, &config.filter, &mut sink)?;
+        }
+
+        let report = self.get_report();
+        if !report.is_empty() && !config.dry_run {
+            report
+                .write(output)
+                .map_err(|err| err.in_stage(Stage::WritePages))?;
+        }
+        Ok(summary)
+    }
+
+    /// Renders the pages changed since `previous` and records them in a new
+    /// manifest.
+    fn write_changed_pages(
+        &self,
+        config: &Config,
+        renderer: &dyn Renderer,
+        previous: &Manifest,
+        sink: &mut dyn PageSink,
+    ) -> Result<()> {
+        let next = Manifest::new(&config.format, config.get_options_hash());
+        let manifest =
+            self.render_changed_pages_to(renderer, &config.filter, next, previous, sink)?;
+
+        if !config.dry_run {
+            manifest
+                .write(Path::new(&config.output))
+                .map_err(|err| err.in_stage(Stage::WritePages))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,31 @@
+//! Converts Tana JSON exports into Markdown for Logseq, Obsidian or plain
+//! CommonMark tools.
+//!
+//! ```
+//! use tana_to_markdown::{config::ExportOptions, renderer::ObsidianRenderer, Export};
+//!
+//! let json = r#"{"formatVersion": 1, "docs": []}"#;
+//! let export = Export::from_json(json, &ExportOptions::default()).unwrap();
+//!
+//! for page in export.render_pages(&ObsidianRenderer::default()).unwrap() {
+//!     println!("{}: {}", page.path.display(), page.content);
+//! }
+//! ```
+
+pub mod block;
+pub(crate) mod block_builder;
+pub mod config;
+pub mod date;
+pub mod error;
+pub mod export;
+pub(crate) mod manifest;
+pub mod node;
+pub(crate) mod node_builder;
+pub(crate) mod page_index;
+pub mod renderer;
+pub mod report;
+pub mod sink;
+pub(crate) mod sync;
+
+pub use error::{Error, Result};
+pub use export::Export;
@@ -1,29 +1,45 @@
-use std::{rc::Rc, time::Instant};
+use std::{path::Path, process, time::Instant};
 
-use block_builder::*;
-use node_builder::*;
-
-mod block;
-mod block_builder;
-mod config;
-mod node;
-mod node_builder;
+use tana_to_markdown::{
+    config::{self, Command, Config, Verbosity},
+    renderer,
+    report::REPORT_FILENAME,
+    Error, Export, Result,
+};
 
 fn main() {
     let time1 = Instant::now();
     let args: Vec<String> = std::env::args().collect();
-    let config = config::Config::new(&args).unwrap();
 
-    let mut node_builder = NodeBuilder::new();
-    node_builder.load_data_source(config.input);
-    node_builder.build_nodes();
+    let config = match Command::parse(&args) {
+        Ok(Command::Export(config)) => *config,
+        Ok(Command::Help) => {
+            println!("{}", config::USAGE);
+            return;
+        }
+        Ok(Command::Version) => {
+            println!("tana-to-markdown {}", env!("CARGO_PKG_VERSION"));
+            return;
+        }
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            process::exit(2);
+        }
+    };
 
-    let page_builder = BlockBuilder::new(Rc::new(node_builder));
-    page_builder.build_blocks();
-    page_builder.write_pages(&config.output);
+    if let Err(err) = run(&config) {
+        eprintln!("Error: {}", err);
+        process::exit(1);
+    }
 
-    let time2 = Instant::now();
-    println!("Finish in {:?}", time2.duration_since(time1));
+    if config.verbosity >= Verbosity::Normal {
+        let time2 = Instant::now();
+        // Stdout carries the pages when streaming, so status goes to stderr.
+        match config.is_stdout() {
+            true => eprintln!("Finish in {:?}", time2.duration_since(time1)),
+            false => println!("Finish in {:?}", time2.duration_since(time1)),
+        }
+    }
 
     // let store = store::Store::new(config.input.clone());
 
@@ -55,3 +71,33 @@ fn main() {
     //     page.write(config.output.clone());
     // });
 }
+
+fn run(config: &Config) -> Result<()> {
+    let renderer = renderer::from_config(config)
+        .ok_or_else(|| Error::Config(format!("Unknown format '{}'", config.format)))?;
+
+    let export = Export::from_config(config)?;
+    let summary = export.write(config, renderer.as_ref())?;
+    if summary.edited > 0 && config.verbosity >= Verbosity::Normal {
+        println!("Files edited since the last export: {}", summary.edited);
+    }
+
+    let report = export.get_report();
+    // Without a report file, the skipped nodes are listed on stderr.
+    if !report.is_empty() && (config.is_stdout() || config.dry_run) {
+        report
+            .get_lines()
+            .iter()
+            .for_each(|line| eprintln!("{}", line));
+        return Ok(());
+    }
+    if !report.is_empty() && config.verbosity >= Verbosity::Normal {
+        eprintln!(
+            "Skipped {} broken nodes, see {}",
+            report.len(),
+            Path::new(&config.output).join(REPORT_FILENAME).display()
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,118 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+pub const MANIFEST_FILENAME: &str = ".tana-to-markdown.json";
+
+/// Records what an export wrote into the output directory, so the next
+/// incremental export only rewrites pages that changed.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    pub format: String,
+    /// Hash of the other settings that change how pages are rendered, see
+    /// `Config::get_options_hash`.
+    pub options: u64,
+    /// Written pages by block id.
+    pub pages: BTreeMap<String, ManifestEntry>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Path of the page relative to the output directory.
+    pub path: PathBuf,
+    /// Latest change among the blocks shown on the page.
+    pub modified: u64,
+    /// Files of the pages the page links to, since a renamed page changes
+    /// the links pointing at it.
+    pub links: Vec<PathBuf>,
+    /// Hash of the ids of the blocks shown on the page, since removing or
+    /// moving a block away changes no `modified` time that is left.
+    pub blocks: u64,
+    /// Hash of the content last exported for the page, to tell whether the
+    /// file was edited since.
+    pub hash: u64,
+}
+
+impl Manifest {
+    pub fn new(format: &str, options: u64) -> Manifest {
+        Manifest {
+            format: format.to_string(),
+            options,
+            pages: BTreeMap::new(),
+        }
+    }
+
+    /// Reads the manifest of an output directory, or an empty one if the
+    /// directory has none yet.
+    pub fn load(output: &Path) -> Result<Manifest> {
+        let filepath = output.join(MANIFEST_FILENAME);
+        if !filepath.exists() {
+            return Ok(Manifest::default());
+        }
+
+        let content = fs::read(&filepath).map_err(|err| Error::io(&filepath, err))?;
+        serde_json::from_slice(&content).map_err(|source| Error::Json {
+            path: Some(filepath),
+            source,
+        })
+    }
+
+    pub fn write(&self, output: &Path) -> Result<()> {
+        let filepath = output.join(MANIFEST_FILENAME);
+        let content = serde_json::to_vec_pretty(self).map_err(|source| Error::Json {
+            path: Some(filepath.clone()),
+            source,
+        })?;
+
+        fs::write(&filepath, content).map_err(|err| Error::io(&filepath, err))
+    }
+
+    pub fn exists(output: &Path) -> bool {
+        output.join(MANIFEST_FILENAME).exists()
+    }
+
+    /// Whether pages were rendered with the same format and settings as
+    /// for `other`.
+    pub fn has_same_settings(&self, other: &Manifest) -> bool {
+        self.format == other.format && self.options == other.options
+    }
+
+    /// Whether the page was written before exactly as it would be now.
+    pub fn is_current(&self, id: &str, entry: &ManifestEntry) -> bool {
+        self.pages.get(id).is_some_and(|previous| {
+            previous.path == entry.path
+                && previous.modified == entry.modified
+                && previous.links == entry.links
+                && previous.blocks == entry.blocks
+        })
+    }
+
+    pub fn get_entry_by_path(&self, path: &Path) -> Option<&ManifestEntry> {
+        self.pages.values().find(|entry| entry.path == path)
+    }
+
+    /// Files this manifest wrote that `next` no longer has.
+    pub fn get_removed_paths(&self, next: &Manifest) -> Vec<PathBuf> {
+        let kept: HashSet<&PathBuf> = next.pages.values().map(|entry| &entry.path).collect();
+
+        self.pages
+            .values()
+            .filter(|entry| !kept.contains(&entry.path))
+            .map(|entry| entry.path.clone())
+            .collect()
+    }
+}
+
+/// FNV-1a hash of a page's content, or of an id to derive a stable one from.
+/// Only compared against itself, so it needs to be stable, not cryptographic.
+pub fn content_hash(content: &[u8]) -> u64 {
+    content.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
@@ -1,6 +1,8 @@
 use std::cell::RefCell;
 use std::rc::Rc;
 
+use crate::error::{Error, NodeIssue, Result};
+
 pub type NodeRef = Rc<RefCell<Node>>;
 
 #[derive(Clone, Debug)]
@@ -13,9 +15,12 @@ pub struct Node {
 #[derive(Clone, Debug)]
 pub struct Props {
     pub created: u64,
+    /// Latest entry of the node's `modifiedTs`.
+    pub modified: Option<u64>,
     pub name: Option<String>,
     pub description: Option<String>,
     pub doc_type: Option<String>,
+    pub done: Option<bool>,
     pub owner_node: Option<NodeRef>,
     pub meta_node: Option<NodeRef>,
     pub source_node: Option<NodeRef>,
@@ -32,7 +37,16 @@ pub enum Meta {
     // // SYS_A14
     // ChildSuperTag,
     // SYS_A15
-    SearchExpression,
+    SearchExpression(Vec<SearchTerm>),
+    // SYS_A70
+    CodeLanguage(String),
+}
+
+#[derive(Clone, Debug)]
+pub enum SearchTerm {
+    Tag(String),
+    Field(String, Vec<String>),
+    Other(String),
 }
 
 #[derive(Clone, Debug)]
@@ -52,123 +66,193 @@ impl Node {
         }
     }
 
-    pub fn get_name(&self) -> Option<String> {
-        self.get_props().name.clone()
+    pub fn get_name(&self) -> Result<Option<String>> {
+        Ok(self.get_props()?.name)
     }
 
-    pub fn get_props(&self) -> Props {
-        if self.props.is_none() {
-            dbg!(&self.id);
-        }
-        self.props.clone().unwrap()
+    pub fn get_props(&self) -> Result<Props> {
+        self.props
+            .clone()
+            .ok_or_else(|| Error::node(&self.id, NodeIssue::MissingProps))
     }
 
     pub fn get_children(&self) -> Vec<NodeRef> {
-        self.children
-            .borrow()
-            .iter()
-            .map(|child| Rc::clone(child))
-            .collect()
+        self.children.borrow().iter().map(Rc::clone).collect()
     }
 
-    pub fn get_doc_type(&self) -> DocType {
-        let doc_type = self.get_props().doc_type;
+    pub fn get_doc_type(&self) -> Result<DocType> {
+        let doc_type = self.get_props()?.doc_type;
 
-        match doc_type.unwrap_or(String::from("")).as_str() {
+        Ok(match doc_type.unwrap_or(String::from("")).as_str() {
             "tuple" => DocType::Tuple,
             "codeblock" => DocType::Codeblock,
             "search" => DocType::Search,
             _ => DocType::Text,
-        }
+        })
+    }
+
+    pub fn get_owner_node(&self) -> Result<Option<NodeRef>> {
+        Ok(self.get_props()?.owner_node)
     }
 
-    pub fn get_owner_node(&self) -> Option<NodeRef> {
-        self.get_props().owner_node
+    /// Owners of the node from its direct owner up to the node without one,
+    /// which is the workspace root for everything in the workspace.
+    pub fn get_owner_chain(&self) -> Result<Vec<NodeRef>> {
+        let mut chain: Vec<NodeRef> = vec![];
+        let mut owner = self.get_owner_node()?;
+
+        while let Some(node) = owner {
+            let is_cycle = node.borrow().id == self.id
+                || chain
+                    .iter()
+                    .any(|other| other.borrow().id == node.borrow().id);
+            if is_cycle {
+                break;
+            }
+            owner = node.borrow().get_owner_node()?;
+            chain.push(node);
+        }
+
+        Ok(chain)
     }
 
-    pub fn get_tag_list(&self) -> Vec<String> {
-        for meta in self.get_meta() {
+    pub fn get_tag_list(&self) -> Result<Vec<String>> {
+        for meta in self.get_meta()? {
             if let Meta::SuperTags(tags) = meta {
-                return tags;
+                return Ok(tags);
             }
         }
-        return vec![];
+        Ok(vec![])
     }
 
-    pub fn get_meta_node(&self) -> Option<NodeRef> {
-        self.get_props().meta_node
+    pub fn get_code_language(&self) -> Result<Option<String>> {
+        Ok(self.get_meta()?.into_iter().find_map(|meta| match meta {
+            Meta::CodeLanguage(language) => Some(language),
+            _ => None,
+        }))
     }
 
-    pub fn get_meta(&self) -> Vec<Meta> {
-        let meta_node = self.get_meta_node();
-        if meta_node.is_none() {
-            return vec![];
-        }
-        let meta_node = meta_node.unwrap();
+    pub fn get_search_expression(&self) -> Result<Option<Vec<SearchTerm>>> {
+        Ok(self.get_meta()?.into_iter().find_map(|meta| match meta {
+            Meta::SearchExpression(terms) => Some(terms),
+            _ => None,
+        }))
+    }
+
+    pub fn get_meta_node(&self) -> Result<Option<NodeRef>> {
+        Ok(self.get_props()?.meta_node)
+    }
+
+    pub fn get_meta(&self) -> Result<Vec<Meta>> {
+        let meta_node = match self.get_meta_node()? {
+            Some(meta_node) => meta_node,
+            None => return Ok(vec![]),
+        };
         let meta_node = meta_node.borrow();
 
-        meta_node
-            .get_children()
-            .iter()
-            .map(|item| Meta::from_node(Rc::clone(item)))
-            .filter(|meta| meta.is_some())
-            .map(|meta| meta.unwrap())
-            .collect::<Vec<Meta>>()
+        let mut meta = vec![];
+        for item in meta_node.get_children() {
+            if let Some(item) = Meta::from_node(item)? {
+                meta.push(item);
+            }
+        }
+        Ok(meta)
     }
 
-    pub fn is_in_trash(&self) -> bool {
+    pub fn is_in_trash(&self) -> Result<bool> {
         if self.id.ends_with("_TRASH") {
-            return true;
+            return Ok(true);
         }
 
-        let mut owner = self.get_owner_node();
+        let mut owner = self.get_owner_node()?;
 
-        while owner.is_some() {
-            if owner.as_ref().unwrap().borrow().id.ends_with("_TRASH") {
-                return true;
+        while let Some(node) = owner {
+            if node.borrow().id.ends_with("_TRASH") {
+                return Ok(true);
             }
-            owner = owner.unwrap().borrow().get_owner_node();
+            owner = node.borrow().get_owner_node()?;
         }
 
-        return false;
+        Ok(false)
     }
 
     pub fn is_sys_node(&self) -> bool {
-        return self.id.starts_with("SYS");
+        self.id.starts_with("SYS")
     }
 }
 
 impl Meta {
-    pub fn from_node(node: NodeRef) -> Option<Meta> {
-        let props = node.borrow().get_props();
+    pub fn from_node(node: NodeRef) -> Result<Option<Meta>> {
+        let props = node.borrow().get_props()?;
         let node = node.borrow();
         let children = node.children.borrow();
 
-        if props.doc_type.as_ref()? == &String::from("tuple") {
-            let kind = &children[0];
+        if props.doc_type.as_deref() != Some("tuple") {
+            return Ok(None);
+        }
 
-            if kind.borrow().id == "SYS_A13" {
-                let tags = children
-                    .iter()
-                    .skip(1)
-                    // .filter(|child| !store.is_sys_node(child))
-                    .map(|child| {
-                        child
-                            .borrow()
-                            .props
-                            .as_ref()
-                            .unwrap()
-                            .name
-                            .as_ref()
-                            .unwrap()
-                            .clone()
-                    })
-                    .collect::<Vec<_>>();
-
-                return Some(Meta::SuperTags(tags));
+        let kind = children
+            .first()
+            .ok_or_else(|| Error::node(&node.id, NodeIssue::MalformedTuple))?;
+
+        if kind.borrow().id == "SYS_A13" {
+            let mut tags = vec![];
+            for child in children.iter().skip(1) {
+                let child = child.borrow();
+                // .filter(|child| !store.is_sys_node(child))
+                let name = child
+                    .get_name()?
+                    .ok_or_else(|| Error::node(&node.id, NodeIssue::MalformedTuple))?;
+                tags.push(name);
+            }
+
+            return Ok(Some(Meta::SuperTags(tags)));
+        }
+
+        if kind.borrow().id == "SYS_A15" {
+            let mut terms = vec![];
+            for child in children.iter().skip(1) {
+                terms.push(SearchTerm::from_node(&child.borrow())?);
+            }
+
+            return Ok(Some(Meta::SearchExpression(terms)));
+        }
+
+        if kind.borrow().id == "SYS_A70" {
+            let language = match children.get(1) {
+                Some(value) => value.borrow().get_name()?,
+                None => None,
+            };
+            return Ok(language.map(Meta::CodeLanguage));
+        }
+
+        Ok(None)
+    }
+}
+
+impl SearchTerm {
+    pub fn from_node(node: &Node) -> Result<SearchTerm> {
+        let name = node.get_name()?.unwrap_or(node.id.clone());
+
+        if node.get_tag_list()?.contains(&String::from("supertag")) {
+            return Ok(SearchTerm::Tag(name));
+        }
+
+        if let DocType::Tuple = node.get_doc_type()? {
+            let children = node.get_children();
+            if let Some(field) = children.first() {
+                let field = field.borrow();
+                let field_name = field.get_name()?.unwrap_or(field.id.clone());
+                let mut values = vec![];
+                for value in children.iter().skip(1) {
+                    if let Some(value) = value.borrow().get_name()? {
+                        values.push(value);
+                    }
+                }
+                return Ok(SearchTerm::Field(field_name, values));
             }
         }
 
-        return None;
+        Ok(SearchTerm::Other(name))
     }
 }
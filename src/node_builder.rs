@@ -1,13 +1,120 @@
-use crate::node::{DocType, Node, NodeRef, Props};
-use std::{cell::RefCell, cmp::Ordering, collections::HashMap, fs, io::Read, rc::Rc};
+use crate::{
+    error::{Error, NodeIssue, Result, Stage},
+    node::{DocType, Node, NodeRef, Props},
+    report::Report,
+};
+use std::{
+    cell::RefCell,
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    fmt, fs,
+    io::{self, BufRead, BufReader, Cursor, Read, Seek},
+    rc::Rc,
+};
+
+use flate2::read::GzDecoder;
+use zip::ZipArchive;
+
+use serde::{
+    de::{self, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
+use serde_json::value::RawValue;
+
+/// Deserializes the top-level export object, handing every entry of `docs` to
+/// the builder as soon as it is parsed instead of collecting them first.
+struct DataSourceSeed<'a> {
+    builder: &'a mut NodeBuilder,
+}
+
+struct DocsSeed<'a> {
+    builder: &'a mut NodeBuilder,
+}
+
+/// How the export JSON is packed, told apart by the first bytes of the input
+/// since archives are often renamed or piped in without a name.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InputFormat {
+    Json,
+    Gzip,
+    Zip,
+}
 
-use serde::{Deserialize, Serialize};
+impl InputFormat {
+    pub fn detect(head: &[u8]) -> InputFormat {
+        if head.starts_with(&[0x1f, 0x8b]) {
+            InputFormat::Gzip
+        } else if head.starts_with(b"PK\x03\x04") || head.starts_with(b"PK\x05\x06") {
+            InputFormat::Zip
+        } else {
+            InputFormat::Json
+        }
+    }
+}
 
-#[derive(Debug, Serialize, Deserialize)]
-struct DataSource {
-    #[serde(rename = "formatVersion")]
-    pub format_version: u64,
-    pub docs: Vec<OriginNode>,
+/// Known layouts of the export, by `formatVersion`. Each one is normalized
+/// into `OriginNode` here, so nothing past loading depends on the layout.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FormatVersion {
+    /// The layout `OriginNode` mirrors directly.
+    V1,
+}
+
+impl FormatVersion {
+    pub const SUPPORTED: &'static [u64] = &[1];
+
+    pub fn from_number(version: u64) -> Option<FormatVersion> {
+        match version {
+            1 => Some(FormatVersion::V1),
+            _ => None,
+        }
+    }
+
+    /// Reads the next entry of `docs` as laid out in this version.
+    fn next_doc<'de, A: SeqAccess<'de>>(
+        self,
+        seq: &mut A,
+    ) -> std::result::Result<Option<OriginNode>, A::Error> {
+        match self {
+            FormatVersion::V1 => seq.next_element::<OriginNode>(),
+        }
+    }
+}
+
+/// Suffixes of the ids Tana gives the structural nodes of a workspace, after
+/// the id of the workspace root and an underscore.
+const CONTAINER_SUFFIXES: [&str; 14] = [
+    "SCHEMA",
+    "TRASH",
+    "STASH",
+    "LIBRARY",
+    "SEARCHES",
+    "WORKSPACE",
+    "USERS",
+    "CAPTURE_INBOX",
+    "MOVETO",
+    "SIDEBAR_AREAS",
+    "QUICK_ADD",
+    "PINS",
+    "CHATDRAFTS",
+    "TRAILING_SIDEBAR",
+];
+
+/// The nodes that give a workspace its structure rather than content.
+#[derive(Clone, Debug, Default)]
+pub struct Workspace {
+    pub root: Option<String>,
+    /// Where Tana keeps documents that aren't part of any other node.
+    pub library: Option<String>,
+    /// Structural nodes like the library, schema and trash, which hang off
+    /// the root with ids made of the root's id and a known suffix.
+    pub containers: HashSet<String>,
+}
+
+impl Workspace {
+    pub fn is_container(&self, id: &str) -> bool {
+        self.containers.contains(id) || self.root.as_deref() == Some(id)
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -34,11 +141,32 @@ pub struct OriginalProps {
     pub meta_node_id: Option<String>,
     #[serde(rename = "_sourceId")]
     pub source_id: Option<String>,
+    /// Completion state of a checkbox: `false` when open, `true` or the
+    /// completion timestamp when done.
+    #[serde(rename = "_done")]
+    pub done: Option<serde_json::Value>,
 }
 
 pub struct NodeBuilder {
     origin_store: HashMap<String, OriginNode>,
+    /// Node ids in the order of the export, so every pass over the nodes is
+    /// deterministic.
+    origin_order: Vec<String>,
     store: Rc<RefCell<HashMap<String, NodeRef>>>,
+    /// `formatVersion` as declared by the export, whether supported or not.
+    format_version: Option<u64>,
+    /// `docs` when it came before `formatVersion`, kept as unparsed text
+    /// until the layout to read it with is known. That text is as large as
+    /// the export, but far smaller than the parsed JSON would be.
+    pending_docs: Option<Box<RawValue>>,
+    lenient: bool,
+    report: Rc<Report>,
+}
+
+impl Default for NodeBuilder {
+    fn default() -> Self {
+        NodeBuilder::new()
+    }
 }
 
 impl NodeBuilder {
@@ -46,37 +174,227 @@ impl NodeBuilder {
         NodeBuilder {
             store: Rc::new(RefCell::new(HashMap::new())),
             origin_store: HashMap::new(),
+            origin_order: vec![],
+            format_version: None,
+            pending_docs: None,
+            lenient: false,
+            report: Rc::new(Report::new()),
         }
     }
 
-    pub fn load_data_source(&mut self, filepath: String) {
-        let mut input_file = fs::OpenOptions::new().read(true).open(filepath).unwrap();
-        let mut input_content = String::new();
-        input_file.read_to_string(&mut input_content).unwrap();
-        let data_source: DataSource = serde_json::from_str(&input_content.clone()).unwrap();
+    /// In lenient mode broken nodes are left out and recorded in the report
+    /// instead of aborting the export.
+    pub fn set_lenient(&mut self, lenient: bool) {
+        self.lenient = lenient;
+    }
+
+    pub fn is_lenient(&self) -> bool {
+        self.lenient
+    }
+
+    pub fn get_report(&self) -> Rc<Report> {
+        Rc::clone(&self.report)
+    }
 
-        data_source.docs.into_iter().for_each(|origin_node| {
-            self.origin_store
-                .insert(origin_node.id.clone(), origin_node);
-        });
+    pub fn get_format_version(&self) -> Option<FormatVersion> {
+        self.format_version.and_then(FormatVersion::from_number)
     }
 
-    pub fn build_nodes(&self) {
-        self.origin_store
-            .values()
-            .for_each(|origin_node| self.build_node(origin_node))
+    /// Loads an export file, which may also be a `.zip` or `.json.gz` holding
+    /// the export.
+    pub fn load_data_source(&mut self, filepath: String) -> Result<()> {
+        let input_file = fs::OpenOptions::new()
+            .read(true)
+            .open(&filepath)
+            .map_err(|err| Error::io(&filepath, err))?;
+
+        let mut reader = BufReader::new(input_file);
+        let result = match reader.fill_buf().map(InputFormat::detect) {
+            // A file can seek to the zip directory instead of being buffered.
+            Ok(InputFormat::Zip) => self.load_zip(reader),
+            Ok(_) => self.load_reader(reader),
+            Err(err) => Err(Error::io(&filepath, err)),
+        };
+
+        result.map_err(|err| match err {
+            Error::Io { source, .. } => Error::io(&filepath, source),
+            Error::Json { source, .. } => Error::Json {
+                path: Some(filepath.into()),
+                source,
+            },
+            err => err,
+        })
+    }
+
+    /// Loads an export from a stream, decompressing gzip and zip input on the
+    /// fly.
+    pub fn load_reader(&mut self, reader: impl Read) -> Result<()> {
+        let mut reader = BufReader::new(reader);
+        let format = reader
+            .fill_buf()
+            .map(InputFormat::detect)
+            .map_err(|err| Error::io("<input>", err))?;
+
+        match format {
+            InputFormat::Json => self.load_json(reader),
+            InputFormat::Gzip => self.load_json(GzDecoder::new(reader)),
+            InputFormat::Zip => {
+                // The zip directory sits at the end, so a stream has to be
+                // read in full before any entry can be opened.
+                let mut content = vec![];
+                reader
+                    .read_to_end(&mut content)
+                    .map_err(|err| Error::io("<input>", err))?;
+                self.load_zip(Cursor::new(content))
+            }
+        }
+    }
+
+    /// Loads the export from the largest `.json` entry of a zip archive.
+    fn load_zip(&mut self, reader: impl Read + Seek) -> Result<()> {
+        let zip_error = |err: zip::result::ZipError| Error::io("<input>", err.into());
+        let mut archive = ZipArchive::new(reader).map_err(zip_error)?;
+
+        let mut export_index = None;
+        let mut export_size = 0;
+        for index in 0..archive.len() {
+            let entry = archive.by_index_raw(index).map_err(zip_error)?;
+            let is_json = entry.is_file()
+                && entry.name().to_lowercase().ends_with(".json")
+                && !entry.name().starts_with("__MACOSX/");
+            if is_json && (export_index.is_none() || entry.size() > export_size) {
+                export_index = Some(index);
+                export_size = entry.size();
+            }
+        }
+
+        let export_index = export_index.ok_or_else(|| {
+            Error::io(
+                "<input>",
+                io::Error::new(io::ErrorKind::InvalidData, "archive contains no .json file"),
+            )
+        })?;
+        let entry = archive.by_index(export_index).map_err(zip_error)?;
+        self.load_json(entry)
+    }
+
+    fn load_json(&mut self, reader: impl Read) -> Result<()> {
+        let mut deserializer = serde_json::Deserializer::from_reader(BufReader::new(reader));
+        self.load(&mut deserializer)
+    }
+
+    pub fn load_str(&mut self, input_content: &str) -> Result<()> {
+        let mut deserializer = serde_json::Deserializer::from_str(input_content);
+        self.load(&mut deserializer)
+    }
+
+    fn load<'de, R: serde_json::de::Read<'de>>(
+        &mut self,
+        deserializer: &mut serde_json::Deserializer<R>,
+    ) -> Result<()> {
+        let result = DataSourceSeed { builder: self }
+            .deserialize(&mut *deserializer)
+            .and_then(|_| deserializer.end());
+
+        if let Some(version) = self.format_version {
+            if FormatVersion::from_number(version).is_none() {
+                return Err(Error::UnsupportedFormatVersion {
+                    version,
+                    supported: FormatVersion::SUPPORTED,
+                });
+            }
+        }
+
+        result.map_err(|source| match source.is_io() {
+            true => Error::io("<input>", source.into()),
+            false => Error::Json { path: None, source },
+        })
+    }
+
+    fn add_origin_node(&mut self, origin_node: OriginNode) {
+        let id = origin_node.id.clone();
+        if self.origin_store.insert(id.clone(), origin_node).is_none() {
+            self.origin_order.push(id);
+        }
+    }
+
+    /// Builds the node graph. The raw export is released afterwards, since
+    /// every node now holds what it needs.
+    pub fn build_nodes(&mut self) -> Result<()> {
+        self.origin_order
+            .iter()
+            .filter_map(|id| self.origin_store.get(id))
+            .try_for_each(|origin_node| self.build_node(origin_node))?;
+
+        self.origin_store = HashMap::new();
+        Ok(())
     }
 
     pub fn get_nodes(&self) -> Vec<NodeRef> {
-        self.store
-            .borrow()
-            .values()
-            .map(|node| Rc::clone(node))
+        let store = self.store.borrow();
+        self.origin_order
+            .iter()
+            .filter_map(|id| store.get(id).map(Rc::clone))
             .collect()
     }
 
-    pub fn get_node(&self, id: &String) -> NodeRef {
-        Rc::clone(self.store.borrow().get(id).unwrap())
+    /// Finds the workspace root, the ownerless node whose containers are
+    /// named after it (`<root>_SCHEMA`, `<root>_TRASH`, ...), falling back to
+    /// the ownerless node with the most children.
+    pub fn get_workspace(&self) -> Result<Workspace> {
+        let store = self.store.borrow();
+        let get_containers = |id: &str| -> HashSet<String> {
+            CONTAINER_SUFFIXES
+                .iter()
+                .map(|suffix| format!("{}_{}", id, suffix))
+                .filter(|container| store.contains_key(container))
+                .collect()
+        };
+
+        // Only the root owns the structural containers, so ownerless nodes
+        // without any, like the pages of a hand-made export, are never it.
+        let mut root = None;
+        for node in store.values() {
+            let node = node.borrow();
+            if node.is_sys_node() || node.get_owner_node()?.is_some() {
+                continue;
+            }
+
+            let containers = get_containers(&node.id);
+            if containers.is_empty() {
+                continue;
+            }
+            let children = node.get_children().len();
+            let is_better = root
+                .as_ref()
+                .is_none_or(|(id, _, most)| (children, &node.id) > (*most, id));
+            if is_better {
+                root = Some((node.id.clone(), containers, children));
+            }
+        }
+
+        let (root, containers, _) = match root {
+            Some(root) => root,
+            None => return Ok(Workspace::default()),
+        };
+        let library = ["STASH", "LIBRARY"]
+            .iter()
+            .map(|suffix| format!("{}_{}", root, suffix))
+            .find(|id| containers.contains(id));
+
+        Ok(Workspace {
+            root: Some(root),
+            library,
+            containers,
+        })
+    }
+
+    pub fn get_node(&self, id: &String) -> Result<NodeRef> {
+        self.store
+            .borrow()
+            .get(id)
+            .map(Rc::clone)
+            .ok_or_else(|| Error::node(id, NodeIssue::MissingNode))
     }
 
     fn add_node(&self, node: NodeRef) {
@@ -89,10 +407,10 @@ impl NodeBuilder {
         self.store.borrow().contains_key(id)
     }
 
-    pub fn build_node(&self, origin_node: &OriginNode) {
+    pub fn build_node(&self, origin_node: &OriginNode) -> Result<()> {
         let node_id = origin_node.id.clone();
-        if self.contains_node(&node_id) {
-            return;
+        if self.contains_node(&node_id) || self.report.is_skipped(&node_id) {
+            return Ok(());
         }
 
         let node_ref: NodeRef = Rc::new(RefCell::new(Node::new(
@@ -103,110 +421,305 @@ impl NodeBuilder {
 
         self.add_node(node_ref);
 
-        self.build_node_props(&origin_node);
+        let result = self
+            .build_node_props(origin_node)
+            .and_then(|_| self.build_node_child(origin_node));
 
-        self.build_node_child(&origin_node);
+        match result {
+            Err(Error::Node { issue, .. }) if self.lenient => {
+                self.store.borrow_mut().remove(&node_id);
+                self.report.skip(&node_id, Stage::BuildNodes, issue);
+                Ok(())
+            }
+            result => result,
+        }
     }
 
-    pub fn build_node_by_id(&self, id: &String) {
-        if self.origin_store.contains_key(id) {
-            let origin_node = self.origin_store.get(id).unwrap();
-            self.build_node(origin_node);
+    pub fn build_node_by_id(&self, id: &String) -> Result<()> {
+        match self.origin_store.get(id) {
+            Some(origin_node) => self.build_node(origin_node),
+            None => Ok(()),
         }
     }
 
-    fn build_node_child(&self, origin_node: &OriginNode) -> Option<bool> {
-        let node_id = &origin_node.id;
-        origin_node
-            .children
-            .as_ref()
-            .unwrap_or(&vec![])
-            .iter()
-            .for_each(|child_id| {
-                let origin_child_node = self.origin_store.get(child_id);
-                if origin_child_node.is_none() {
-                    return;
-                }
-
-                self.build_node(origin_child_node.unwrap());
-
-                let child_node = self.get_node(child_id);
-
-                self.get_node(node_id)
-                    .borrow_mut()
-                    .children
-                    .borrow_mut()
-                    .push(child_node);
-            });
+    fn build_node_child(&self, origin_node: &OriginNode) -> Result<()> {
+        let node = self.get_node(&origin_node.id)?;
 
-        self.get_node(node_id)
-            .borrow()
-            .children
-            .borrow_mut()
-            .sort_by(|a, b| {
-                let mut a_is_tuple = false;
-                let mut b_is_tuple = false;
+        for child_id in origin_node.children.as_ref().unwrap_or(&vec![]) {
+            let origin_child_node = match self.origin_store.get(child_id) {
+                Some(origin_child_node) => origin_child_node,
+                None => continue,
+            };
 
-                if let DocType::Tuple = a.borrow().get_doc_type() {
-                    a_is_tuple = true;
-                }
+            self.build_node(origin_child_node)?;
+            if !self.contains_node(child_id) {
+                continue;
+            }
 
-                if let DocType::Tuple = b.borrow().get_doc_type() {
-                    b_is_tuple = true;
-                }
+            let child_node = self.get_node(child_id)?;
+            node.borrow_mut().children.borrow_mut().push(child_node);
+        }
 
-                match (a_is_tuple, b_is_tuple) {
-                    (true, false) => Ordering::Less,
-                    (false, true) => Ordering::Greater,
-                    (_, _) => Ordering::Equal,
-                }
-            });
+        // Tuple children are positional (key first, then values), so only
+        // regular nodes get their fields moved in front of their content.
+        if let DocType::Tuple = node.borrow().get_doc_type()? {
+            if node.borrow().children.borrow().is_empty() {
+                return Err(Error::node(&origin_node.id, NodeIssue::MalformedTuple));
+            }
+            return Ok(());
+        }
 
-        Some(true)
+        let mut children = node
+            .borrow()
+            .get_children()
+            .into_iter()
+            .map(|child| {
+                let is_tuple = matches!(child.borrow().get_doc_type()?, DocType::Tuple);
+                Ok((is_tuple, child))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        children.sort_by(
+            |(a_is_tuple, _), (b_is_tuple, _)| match (a_is_tuple, b_is_tuple) {
+                (true, false) => Ordering::Less,
+                (false, true) => Ordering::Greater,
+                (_, _) => Ordering::Equal,
+            },
+        );
+
+        *node.borrow().children.borrow_mut() =
+            children.into_iter().map(|(_, child)| child).collect();
+
+        Ok(())
     }
 
-    fn build_node_props(&self, origin_node: &OriginNode) -> Option<bool> {
+    fn build_node_props(&self, origin_node: &OriginNode) -> Result<()> {
         let node_id = &origin_node.id;
-        let origin_props = origin_node.props.clone()?;
+        let origin_props = origin_node
+            .props
+            .clone()
+            .ok_or_else(|| Error::node(node_id, NodeIssue::MissingProps))?;
 
         let mut props = Props {
             created: origin_props.created,
+            modified: origin_node
+                .modified_ts
+                .as_ref()
+                .and_then(|timestamps| timestamps.iter().max().copied()),
             name: origin_props.name,
             description: origin_props.description,
             doc_type: origin_props.doc_type,
+            done: origin_props.done.map(|done| match done {
+                serde_json::Value::Bool(done) => done,
+                serde_json::Value::Number(timestamp) => timestamp.as_u64() != Some(0),
+                _ => false,
+            }),
             owner_node: None,
             meta_node: None,
             source_node: None,
         };
 
-        self.get_node(node_id).borrow_mut().props = Some(props.clone());
+        self.get_node(node_id)?.borrow_mut().props = Some(props.clone());
 
-        if origin_props.owner_id.is_some() {
-            let owner_id = &origin_props.owner_id?;
-            self.build_node_by_id(owner_id);
-            if self.contains_node(owner_id) {
-                props.owner_node = Some(self.get_node(owner_id));
-            }
+        if let Some(owner_id) = &origin_props.owner_id {
+            props.owner_node = self.build_reference(node_id, "_ownerId", owner_id)?;
         }
 
-        if origin_props.meta_node_id.is_some() {
-            let meta_id = &origin_props.meta_node_id?;
-            self.build_node_by_id(meta_id);
-            if self.contains_node(meta_id) {
-                props.meta_node = Some(self.get_node(meta_id));
+        if let Some(meta_id) = &origin_props.meta_node_id {
+            props.meta_node = self.build_reference(node_id, "_metaNodeId", meta_id)?;
+        }
+
+        if let Some(source_id) = &origin_props.source_id {
+            props.source_node = self.build_reference(node_id, "_sourceId", source_id)?;
+        }
+
+        self.get_node(node_id)?.borrow_mut().props = Some(props);
+
+        Ok(())
+    }
+
+    /// Resolves a reference held in one of the node's props. A reference to a
+    /// node that is absent from the export is dropped, except in lenient mode,
+    /// where the node is skipped and reported instead. A reference to a node
+    /// that was skipped is always dropped.
+    fn build_reference(
+        &self,
+        node_id: &str,
+        field: &'static str,
+        target_id: &String,
+    ) -> Result<Option<NodeRef>> {
+        if !self.origin_store.contains_key(target_id) && !self.lenient {
+            return Ok(None);
+        }
+        if !self.origin_store.contains_key(target_id) {
+            return Err(Error::node(
+                node_id,
+                NodeIssue::DanglingReference {
+                    field,
+                    target: target_id.clone(),
+                },
+            ));
+        }
+
+        self.build_node_by_id(target_id)?;
+        if !self.contains_node(target_id) {
+            return Ok(None);
+        }
+
+        Ok(Some(self.get_node(target_id)?))
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for DataSourceSeed<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> std::result::Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for DataSourceSeed<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a Tana export object")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> std::result::Result<(), A::Error> {
+        let mut has_format_version = false;
+        let mut has_docs = false;
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "formatVersion" => {
+                    let version = map.next_value::<u64>()?;
+                    self.builder.format_version = Some(version);
+                    if FormatVersion::from_number(version).is_none() {
+                        return Err(de::Error::custom("unsupported formatVersion"));
+                    }
+                    has_format_version = true;
+                }
+                "docs" if self.builder.format_version.is_none() => {
+                    self.builder.pending_docs = Some(map.next_value::<Box<RawValue>>()?);
+                    has_docs = true;
+                }
+                "docs" => {
+                    map.next_value_seed(DocsSeed {
+                        builder: &mut *self.builder,
+                    })?;
+                    has_docs = true;
+                }
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
             }
         }
 
-        if origin_props.source_id.is_some() {
-            let source_id = &origin_props.source_id?;
-            self.build_node_by_id(source_id);
-            if self.contains_node(source_id) {
-                props.source_node = Some(self.get_node(source_id));
+        if !has_format_version {
+            return Err(de::Error::missing_field("formatVersion"));
+        }
+        if !has_docs {
+            return Err(de::Error::missing_field("docs"));
+        }
+
+        if let Some(docs) = self.builder.pending_docs.take() {
+            let mut deserializer = serde_json::Deserializer::from_str(docs.get());
+            DocsSeed {
+                builder: &mut *self.builder,
             }
+            .deserialize(&mut deserializer)
+            .map_err(de::Error::custom)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for DocsSeed<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> std::result::Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for DocsSeed<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a list of Tana nodes")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<(), A::Error> {
+        let version = self
+            .builder
+            .get_format_version()
+            .ok_or_else(|| de::Error::custom("docs read before formatVersion"))?;
+
+        while let Some(origin_node) = version.next_doc(&mut seq)? {
+            self.builder.add_origin_node(origin_node);
         }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(input: &str) -> Result<NodeBuilder> {
+        let mut node_builder = NodeBuilder::new();
+        node_builder.load_str(input)?;
+        Ok(node_builder)
+    }
+
+    #[test]
+    fn unsupported_format_version_is_an_error() {
+        let result = load(r#"{"formatVersion": 2, "docs": []}"#);
+
+        assert!(matches!(
+            result,
+            Err(Error::UnsupportedFormatVersion { version: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn unsupported_format_version_after_docs_is_an_error() {
+        let input = r#"{"docs": [{"id": "a", "props": {"created": 1}}], "formatVersion": 2}"#;
+
+        assert!(matches!(
+            load(input),
+            Err(Error::UnsupportedFormatVersion { version: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn docs_before_format_version_are_loaded() {
+        let input = r#"{
+            "docs": [
+                {"id": "a", "props": {"created": 1, "name": "A"}, "children": ["b"]},
+                {"id": "b", "props": {"created": 2, "name": "B", "_ownerId": "a"}}
+            ],
+            "formatVersion": 1
+        }"#;
+        let mut node_builder = load(input).unwrap();
+        node_builder.build_nodes().unwrap();
+
+        assert_eq!(node_builder.get_format_version(), Some(FormatVersion::V1));
+        let nodes = node_builder.get_nodes();
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[0].borrow().get_children()[0].borrow().id, "b");
+    }
 
-        self.get_node(node_id).borrow_mut().props = Some(props.clone());
+    #[test]
+    fn docs_without_format_version_are_an_error() {
+        let result = load(r#"{"docs": [{"id": "a", "props": {"created": 1}}]}"#);
 
-        Some(true)
+        assert!(matches!(result, Err(Error::Json { .. })));
     }
 }
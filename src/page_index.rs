@@ -0,0 +1,592 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Component, Path, PathBuf},
+    rc::Rc,
+};
+
+use crate::{block::*, config::LinkOptions, date::Date, node::DocType, renderer::Renderer};
+
+const MAX_FILENAME_LEN: usize = 200;
+
+#[derive(Clone)]
+struct PageEntry {
+    name: String,
+    path: PathBuf,
+}
+
+/// A reference to a page from another page.
+pub struct Backlink {
+    pub page: BlockRef,
+    /// The bullet the reference appears in, `None` when it is in the title of
+    /// the referencing page or placed directly on it.
+    pub context: Option<BlockRef>,
+}
+
+/// Maps every exported page to a unique name and the file it is written to,
+/// relative to the output directory. Every link goes through this index so
+/// links keep resolving after titles are sanitized or disambiguated.
+pub struct PageIndex {
+    entries: HashMap<String, PageEntry>,
+    /// Journal page ids by date.
+    journals: HashMap<Date, String>,
+    /// Page each block is written on, by block id.
+    homes: HashMap<String, String>,
+    /// Blocks that aren't pages but are written with an id, so references
+    /// can point at them.
+    block_ids: HashSet<String>,
+    /// References to every page from other pages, by page id.
+    backlinks: HashMap<String, Vec<Backlink>>,
+}
+
+impl PageIndex {
+    /// Journal pages are named and placed the way `renderer`'s target
+    /// expects them.
+    pub fn new(pages: &[BlockRef], renderer: &dyn Renderer, links: &LinkOptions) -> PageIndex {
+        let mut page_refs = pages.iter().collect::<Vec<_>>();
+        // The oldest page keeps the plain name, so the outcome doesn't depend
+        // on the order pages were built in.
+        page_refs.sort_by(|a, b| {
+            let (a, b) = (a.borrow(), b.borrow());
+            a.created.cmp(&b.created).then_with(|| a.id.cmp(&b.id))
+        });
+        let pages = page_refs
+            .iter()
+            .map(|page| page.borrow())
+            .collect::<Vec<_>>();
+
+        let mut naming = Naming {
+            renderer,
+            pages: pages
+                .iter()
+                .map(|page| (page.id.clone(), &**page))
+                .collect(),
+            taken: HashSet::new(),
+            dirs: HashMap::new(),
+            folders: HashMap::new(),
+            entries: HashMap::new(),
+            journals: HashMap::new(),
+        };
+        pages.iter().for_each(|page| naming.add_entry(page));
+        let Naming {
+            entries, journals, ..
+        } = naming;
+
+        let mut index = PageIndex {
+            entries,
+            journals,
+            homes: HashMap::new(),
+            block_ids: HashSet::new(),
+            backlinks: HashMap::new(),
+        };
+        page_refs.iter().for_each(|page| {
+            index.add_inline_backlinks(page, &page.borrow(), None);
+            index.index_blocks(page, &page.borrow(), None, links);
+        });
+        index
+    }
+
+    /// Records the page as the home of the blocks under `block`, down to the
+    /// next pages, and the pages they reference. Also records which blocks
+    /// need an id: blocks embedded by reference nodes always do, other
+    /// referenced blocks only with `links.block_ids`.
+    fn index_blocks(
+        &mut self,
+        page: &BlockRef,
+        block: &Block,
+        parent: Option<&BlockRef>,
+        links: &LinkOptions,
+    ) {
+        let page_id = page.borrow().id.clone();
+
+        block.get_children().iter().for_each(|child_ref| {
+            let child = child_ref.borrow();
+            if child.is_page() {
+                return;
+            }
+
+            if let Some(source) = &child.source {
+                let source = source.borrow();
+                if source.is_page() {
+                    self.add_backlink(&source.id, page, parent);
+                } else if can_have_id(&source) {
+                    self.block_ids.insert(source.id.clone());
+                }
+            }
+            if links.block_ids && child.ref_count > 0 && can_have_id(&child) {
+                self.block_ids.insert(child.id.clone());
+            }
+
+            self.homes.insert(child.id.clone(), page_id.clone());
+            self.add_inline_backlinks(page, &child, Some(child_ref));
+            // Search results aren't shown on the page.
+            if !matches!(child.doc_type, DocType::Search) {
+                self.index_blocks(page, &child, Some(child_ref), links);
+            }
+        });
+    }
+
+    fn add_inline_backlinks(&mut self, page: &BlockRef, block: &Block, context: Option<&BlockRef>) {
+        block.inlines.iter().for_each(|inline| {
+            let target_id = match inline {
+                Inline::Ref(target) => Some(target.borrow().id.clone()),
+                Inline::Date(date) => self.get_journal(date).map(String::from),
+                Inline::Text(_) => None,
+            };
+            if let Some(target_id) = target_id {
+                self.add_backlink(&target_id, page, context);
+            }
+        });
+    }
+
+    fn add_backlink(&mut self, target_id: &str, page: &BlockRef, context: Option<&BlockRef>) {
+        let page_id = page.borrow().id.clone();
+        if target_id == page_id || !self.entries.contains_key(target_id) {
+            return;
+        }
+
+        let context_id = context.map(|context| context.borrow().id.clone());
+        let backlinks = self.backlinks.entry(target_id.to_string()).or_default();
+        let is_known = backlinks.iter().any(|backlink| {
+            backlink.page.borrow().id == page_id
+                && backlink
+                    .context
+                    .as_ref()
+                    .map(|context| context.borrow().id.clone())
+                    == context_id
+        });
+
+        if !is_known {
+            backlinks.push(Backlink {
+                page: Rc::clone(page),
+                context: context.map(Rc::clone),
+            });
+        }
+    }
+
+    /// References to the page from other pages, in the order the referencing
+    /// pages were created.
+    pub fn get_backlinks(&self, id: &str) -> &[Backlink] {
+        self.backlinks
+            .get(id)
+            .map(|backlinks| backlinks.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Id of the page the block is written on, if that page is exported.
+    pub fn get_home(&self, id: &str) -> Option<&str> {
+        self.homes.get(id).map(|id| id.as_str())
+    }
+
+    /// Whether the block is written with an id references can point at.
+    pub fn has_block_id(&self, id: &str) -> bool {
+        self.block_ids.contains(id)
+    }
+
+    /// Id of the journal page of `date`, if it is exported.
+    pub fn get_journal(&self, date: &Date) -> Option<&str> {
+        self.journals.get(date).map(|id| id.as_str())
+    }
+
+    /// Unique name of the page, used wherever the target identifies pages by
+    /// name rather than by file.
+    pub fn get_name(&self, id: &str) -> Option<&str> {
+        self.entries.get(id).map(|entry| entry.name.as_str())
+    }
+
+    pub fn get_path(&self, id: &str) -> Option<&PathBuf> {
+        self.entries.get(id).map(|entry| &entry.path)
+    }
+
+    /// Path of `to_id`'s file relative to the directory containing `from_id`'s file.
+    pub fn get_relative_path(&self, from_id: &str, to_id: &str) -> Option<PathBuf> {
+        let to = self.get_path(to_id)?;
+        let from_dir = self
+            .get_path(from_id)
+            .and_then(|from| from.parent())
+            .unwrap_or(Path::new(""));
+
+        let from_components: Vec<Component> = from_dir.components().collect();
+        let to_components: Vec<Component> = to.components().collect();
+        let common = from_components
+            .iter()
+            .zip(to_components.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        let mut relative = PathBuf::new();
+        from_components[common..]
+            .iter()
+            .for_each(|_| relative.push(".."));
+        to_components[common..]
+            .iter()
+            .for_each(|component| relative.push(component));
+
+        Some(relative)
+    }
+}
+
+/// Assigns the unique names and files of `PageIndex` entries. Pages filed
+/// under another page are named after that page's entry, so they follow it
+/// when it is disambiguated.
+struct Naming<'a> {
+    renderer: &'a dyn Renderer,
+    pages: HashMap<String, &'a Block>,
+    /// Lowercased file paths already taken by a page.
+    taken: HashSet<String>,
+    /// Owner id of every folder, by lowercased path. Pages claim the folder
+    /// next to their file for the pages filed under them.
+    dirs: HashMap<String, String>,
+    /// Resolved folders by owner id, with the path of the folder rather than
+    /// of a file.
+    folders: HashMap<String, PageEntry>,
+    entries: HashMap<String, PageEntry>,
+    journals: HashMap<Date, String>,
+}
+
+impl Naming<'_> {
+    fn add_entry(&mut self, page: &Block) {
+        if self.entries.contains_key(&page.id) {
+            return;
+        }
+
+        let parent = self.get_parent_folder(page);
+        let (mut name, mut path) = match (page.journal, parent) {
+            (Some(date), _) => (
+                self.renderer.get_journal_name(&date),
+                self.renderer.get_journal_path(&date),
+            ),
+            // Logseq stores namespace `a/b/c` in the file `a___b___c.md`.
+            (None, Some(parent)) if self.renderer.uses_namespaces() => (
+                format!("{}/{}", parent.name, page.title),
+                PathBuf::from(format!(
+                    "{}___{}.md",
+                    parent.path.to_string_lossy(),
+                    sanitize_filename(&page.title)
+                )),
+            ),
+            (None, parent) => {
+                let mut path = parent.map(|parent| parent.path).unwrap_or_default();
+                path.push(format!("{}.md", sanitize_filename(&page.title)));
+                (page.title.clone(), path)
+            }
+        };
+
+        // File systems are frequently case-insensitive, and so are Logseq
+        // page names.
+        // The id suffix can itself clash with another page's title, so a
+        // counter follows it until the file is free.
+        if !self.taken.insert(path.to_string_lossy().to_lowercase()) {
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            let stem = format!("{} ({})", stem, sanitize_filename(&page.id));
+            let base_name = format!("{} ({})", name, page.id);
+            let mut counter = 1;
+            loop {
+                let suffix = match counter {
+                    1 => String::new(),
+                    counter => format!(" {}", counter),
+                };
+                let candidate = path.with_file_name(format!("{}{}.md", stem, suffix));
+                if self
+                    .taken
+                    .insert(candidate.to_string_lossy().to_lowercase())
+                {
+                    name = format!("{}{}", base_name, suffix);
+                    path = candidate;
+                    break;
+                }
+                counter += 1;
+            }
+        } else if let Some(date) = page.journal {
+            self.journals.insert(date, page.id.clone());
+        }
+
+        let dir = path.with_extension("").to_string_lossy().to_lowercase();
+        self.dirs.entry(dir).or_insert_with(|| page.id.clone());
+        self.entries
+            .insert(page.id.clone(), PageEntry { name, path });
+    }
+
+    /// The innermost folder of the page, if it is filed under any.
+    fn get_parent_folder(&mut self, page: &Block) -> Option<PageEntry> {
+        let mut parent: Option<PageEntry> = None;
+        for folder in &page.folders {
+            parent = Some(self.get_folder(folder, parent.as_ref()));
+        }
+        parent
+    }
+
+    /// A page keeps its own name as folder. Any other owner is named after
+    /// its title, with slashes replaced so they don't add folders or
+    /// namespace levels, and with its id when another owner already has a
+    /// folder by that name.
+    fn get_folder(&mut self, folder: &Folder, parent: Option<&PageEntry>) -> PageEntry {
+        if let Some(entry) = self.folders.get(&folder.id) {
+            return entry.clone();
+        }
+
+        let entry = match self.pages.get(&folder.id).copied() {
+            Some(page) => {
+                self.add_entry(page);
+                let entry = &self.entries[&folder.id];
+                PageEntry {
+                    name: entry.name.clone(),
+                    path: entry.path.with_extension(""),
+                }
+            }
+            None => {
+                let title = folder.title.replace('/', "_");
+                let folder_entry = |title: &str| {
+                    let file_name = sanitize_filename(title);
+                    match parent {
+                        Some(parent) if self.renderer.uses_namespaces() => PageEntry {
+                            name: format!("{}/{}", parent.name, title),
+                            path: PathBuf::from(format!(
+                                "{}___{}",
+                                parent.path.to_string_lossy(),
+                                file_name
+                            )),
+                        },
+                        Some(parent) => PageEntry {
+                            name: format!("{}/{}", parent.name, title),
+                            path: parent.path.join(file_name),
+                        },
+                        None => PageEntry {
+                            name: title.to_string(),
+                            path: PathBuf::from(file_name),
+                        },
+                    }
+                };
+
+                let mut counter = 0;
+                loop {
+                    let candidate = match counter {
+                        0 => folder_entry(&title),
+                        1 => folder_entry(&format!("{} ({})", title, folder.id)),
+                        counter => folder_entry(&format!("{} ({}) {}", title, folder.id, counter)),
+                    };
+                    let dir = candidate.path.to_string_lossy().to_lowercase();
+                    if self.dirs.get(&dir).is_none_or(|id| id == &folder.id) {
+                        self.dirs.insert(dir, folder.id.clone());
+                        break candidate;
+                    }
+                    counter += 1;
+                }
+            }
+        };
+
+        self.folders.insert(folder.id.clone(), entry.clone());
+        entry
+    }
+}
+
+/// Code blocks and searches are written without a line an id could go on.
+fn can_have_id(block: &Block) -> bool {
+    matches!(block.doc_type, DocType::Text)
+}
+
+/// Turns a title into a file name that is valid on every common file system.
+pub fn sanitize_filename(title: &str) -> String {
+    let mut filename: String = title
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+
+    filename = filename
+        .trim_start_matches(['.', ' '])
+        .trim_end_matches(['.', ' '])
+        .to_string();
+
+    if filename.len() > MAX_FILENAME_LEN {
+        let mut end = MAX_FILENAME_LEN;
+        while !filename.is_char_boundary(end) {
+            end -= 1;
+        }
+        filename.truncate(end);
+    }
+
+    if filename.is_empty() {
+        return String::from("untitled");
+    }
+
+    let reserved = [
+        "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+        "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+    ];
+    let base = filename.split('.').next().unwrap_or("");
+    if reserved.iter().any(|name| name.eq_ignore_ascii_case(base)) {
+        filename.push('_');
+    }
+
+    filename
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use crate::renderer::{LogseqRenderer, MarkdownRenderer};
+
+    use super::*;
+
+    /// `folders` are pairs of owner id and title.
+    fn page(id: &str, title: &str, folders: &[(&str, &str)], created: u64) -> BlockRef {
+        Rc::new(RefCell::new(Block {
+            id: id.to_string(),
+            title: title.to_string(),
+            inlines: vec![],
+            description: None,
+            tags: vec![],
+            metadata: vec![],
+            children: RefCell::new(vec![]),
+            doc_type: DocType::Text,
+            language: None,
+            search: None,
+            todo: None,
+            journal: None,
+            page: true,
+            folders: folders
+                .iter()
+                .map(|(id, title)| Folder {
+                    id: id.to_string(),
+                    title: title.to_string(),
+                })
+                .collect(),
+            source: None,
+            created,
+            modified: created,
+            ref_count: 0,
+        }))
+    }
+
+    fn index(pages: &[BlockRef]) -> PageIndex {
+        PageIndex::new(pages, &MarkdownRenderer, &LinkOptions::default())
+    }
+
+    #[test]
+    fn relative_paths_climb_out_of_folders() {
+        let index = index(&[
+            page("a", "A", &[], 0),
+            page("b", "B", &[("lib", "Library")], 0),
+            page("c", "C", &[("lib", "Library"), ("projects", "Projects")], 0),
+            page("d", "D", &[("archive", "Archive")], 0),
+        ]);
+
+        let path = |from: &str, to: &str| index.get_relative_path(from, to);
+        assert_eq!(path("a", "b"), Some(PathBuf::from("Library/B.md")));
+        assert_eq!(path("b", "a"), Some(PathBuf::from("../A.md")));
+        assert_eq!(path("c", "a"), Some(PathBuf::from("../../A.md")));
+        assert_eq!(path("c", "b"), Some(PathBuf::from("../B.md")));
+        assert_eq!(path("b", "c"), Some(PathBuf::from("Projects/C.md")));
+        assert_eq!(path("c", "d"), Some(PathBuf::from("../../Archive/D.md")));
+        assert_eq!(path("a", "missing"), None);
+    }
+
+    #[test]
+    fn relative_paths_point_at_disambiguated_files() {
+        let index = index(&[page("new", "beta", &[], 2), page("old", "Beta", &[], 1)]);
+
+        assert_eq!(
+            index.get_relative_path("old", "new"),
+            Some(PathBuf::from("beta (new).md"))
+        );
+        assert_eq!(
+            index.get_relative_path("new", "old"),
+            Some(PathBuf::from("Beta.md"))
+        );
+    }
+
+    #[test]
+    fn disambiguated_files_never_clash_with_other_titles() {
+        let pages = [
+            page("a", "Beta", &[], 1),
+            page("b", "Beta (c)", &[], 2),
+            page("c", "Beta", &[], 3),
+            page("d", "beta", &[], 4),
+        ];
+        let index = index(&pages);
+
+        let path = |id: &str| index.get_path(id).cloned();
+        assert_eq!(path("a"), Some(PathBuf::from("Beta.md")));
+        assert_eq!(path("b"), Some(PathBuf::from("Beta (c).md")));
+        assert_eq!(path("c"), Some(PathBuf::from("Beta (c) 2.md")));
+        assert_eq!(path("d"), Some(PathBuf::from("beta (d).md")));
+        assert_eq!(index.get_name("c"), Some("Beta (c) 2"));
+    }
+
+    #[test]
+    fn pages_follow_the_name_of_the_page_they_are_filed_under() {
+        let pages = [
+            page("old", "Beta", &[], 1),
+            page("new", "beta", &[], 2),
+            page("child", "Child", &[("new", "beta")], 3),
+        ];
+
+        let index = index(&pages);
+        assert_eq!(
+            index.get_path("child"),
+            Some(&PathBuf::from("beta (new)/Child.md"))
+        );
+
+        let index = PageIndex::new(&pages, &LogseqRenderer, &LinkOptions::default());
+        assert_eq!(index.get_name("child"), Some("beta (new)/Child"));
+        assert_eq!(
+            index.get_path("child"),
+            Some(&PathBuf::from("beta (new)___Child.md"))
+        );
+    }
+
+    #[test]
+    fn owners_sharing_a_title_keep_separate_folders() {
+        let index = index(&[
+            page("a", "A", &[("notes", "Notes")], 1),
+            page("b", "B", &[("other", "notes")], 2),
+            page("c", "C", &[("notes", "Notes")], 3),
+        ]);
+
+        let path = |id: &str| index.get_path(id).cloned();
+        assert_eq!(path("a"), Some(PathBuf::from("Notes/A.md")));
+        assert_eq!(path("b"), Some(PathBuf::from("notes (other)/B.md")));
+        assert_eq!(path("c"), Some(PathBuf::from("Notes/C.md")));
+    }
+
+    #[test]
+    fn slashes_in_owner_titles_add_no_namespace_levels() {
+        let pages = [page("a", "A", &[("owner", "Q1/Q2")], 1)];
+
+        let index = PageIndex::new(&pages, &LogseqRenderer, &LinkOptions::default());
+        assert_eq!(index.get_name("a"), Some("Q1_Q2/A"));
+        assert_eq!(index.get_path("a"), Some(&PathBuf::from("Q1_Q2___A.md")));
+    }
+
+    #[test]
+    fn sanitize_replaces_reserved_characters() {
+        assert_eq!(sanitize_filename("Alpha: plan/v1?"), "Alpha_ plan_v1_");
+        assert_eq!(sanitize_filename("a\\b*c\"d<e>f|g\th"), "a_b_c_d_e_f_g_h");
+    }
+
+    #[test]
+    fn sanitize_trims_dots_and_spaces() {
+        assert_eq!(sanitize_filename(" ..hidden. "), "hidden");
+        assert_eq!(sanitize_filename(" . "), "untitled");
+        assert_eq!(sanitize_filename(""), "untitled");
+    }
+
+    #[test]
+    fn sanitize_avoids_reserved_windows_names() {
+        assert_eq!(sanitize_filename("con"), "con_");
+        assert_eq!(sanitize_filename("LPT1.notes"), "LPT1.notes_");
+        assert_eq!(sanitize_filename("console"), "console");
+    }
+
+    #[test]
+    fn sanitize_truncates_long_titles_on_char_boundaries() {
+        let filename = sanitize_filename(&"é".repeat(150));
+
+        assert_eq!(filename.len(), MAX_FILENAME_LEN);
+        assert_eq!(filename, "é".repeat(100));
+    }
+}
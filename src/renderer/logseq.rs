@@ -0,0 +1,104 @@
+use std::path::PathBuf;
+
+use crate::{block::*, date::Date, manifest::content_hash, node::SearchTerm};
+
+use super::{RenderContext, Renderer};
+
+/// Logseq's default `:journal/page-title-format`.
+const JOURNAL_TITLE_FORMAT: &str = "MMM Do, YYYY";
+
+pub struct LogseqRenderer;
+
+impl Renderer for LogseqRenderer {
+    fn render_link(&self, block: &Block, ctx: &RenderContext) -> String {
+        let name = ctx.pages.get_name(&block.id).unwrap_or(&block.title);
+        format!("[[{}]]", name)
+    }
+
+    fn get_journal_path(&self, date: &Date) -> PathBuf {
+        PathBuf::from(format!("journals/{}.md", date.format("YYYY_MM_DD")))
+    }
+
+    fn get_journal_name(&self, date: &Date) -> String {
+        date.format(JOURNAL_TITLE_FORMAT)
+    }
+
+    fn uses_namespaces(&self) -> bool {
+        true
+    }
+
+    fn render_date(&self, date: &Date, _ctx: &RenderContext) -> String {
+        format!("[[{}]]", self.get_journal_name(date))
+    }
+
+    fn render_block_ref(&self, block: &Block, _home_id: &str, _ctx: &RenderContext) -> String {
+        format!("(({}))", block_uuid(&block.id))
+    }
+
+    fn render_block_id(&self, line: String, block: &Block, level: usize) -> Vec<String> {
+        let prefix = "  ".repeat(level);
+        vec![line, format!("{}  id:: {}", prefix, block_uuid(&block.id))]
+    }
+
+    fn render_task_marker(&self, done: bool) -> String {
+        String::from(if done { "DONE" } else { "TODO" })
+    }
+
+    fn render_query(&self, terms: &[SearchTerm]) -> Option<Vec<String>> {
+        // Fields are exported as plain bullets rather than Logseq properties,
+        // so only tag terms can be queried.
+        let tags = terms
+            .iter()
+            .map(|term| match term {
+                SearchTerm::Tag(tag) => Some(format!("[[{}]]", tag)),
+                _ => None,
+            })
+            .collect::<Option<Vec<String>>>()?;
+
+        match tags.len() {
+            0 => None,
+            1 => Some(vec![format!("{{{{query {}}}}}", tags[0])]),
+            _ => Some(vec![format!("{{{{query (and {})}}}}", tags.join(" "))]),
+        }
+    }
+
+    fn render_page(&self, block: &Block, ctx: &RenderContext) -> Vec<String> {
+        let name = ctx.pages.get_name(&block.id).unwrap_or(&block.title);
+        // Journal pages are named after their file by Logseq.
+        let mut content = match block.journal {
+            Some(_) => vec![],
+            None => vec![format!("title:: {}", name)],
+        };
+
+        if !block.tags.is_empty() {
+            let tags = block
+                .tags
+                .iter()
+                .map(|tag| self.render_tag(tag))
+                .collect::<Vec<String>>()
+                .join(" ");
+            content.push(format!("tags:: {}", tags));
+        }
+
+        let fields = block.metadata.iter().collect::<Vec<_>>();
+        content.append(&mut self.render_fields(&fields, block.description.as_ref(), 0, ctx));
+        content.append(&mut self.render_children(block, 0, ctx));
+        content.append(&mut self.render_backlinks(block, ctx));
+        content
+    }
+}
+
+/// Logseq only accepts UUIDs as block ids, so one is derived from the Tana id
+/// to keep references stable across exports.
+fn block_uuid(id: &str) -> String {
+    let high = content_hash(id.as_bytes());
+    let low = content_hash(format!("{}#", id).as_bytes());
+    format!(
+        "{:08x}-{:04x}-4{:03x}-{:04x}-{:012x}",
+        high >> 32,
+        (high >> 16) & 0xffff,
+        high & 0xfff,
+        (low >> 48) & 0x3fff | 0x8000,
+        low & 0xffff_ffff_ffff
+    )
+}
@@ -0,0 +1,120 @@
+use crate::{block::*, date::Date};
+
+use super::{RenderContext, Renderer};
+
+/// Plain CommonMark output. References become relative links to the files
+/// written for the referenced pages, so no wiki-style tooling is needed.
+pub struct MarkdownRenderer;
+
+impl Renderer for MarkdownRenderer {
+    fn render_link(&self, block: &Block, ctx: &RenderContext) -> String {
+        if ctx.single_stream {
+            if let Some(name) = ctx.pages.get_name(&block.id) {
+                return format!("[{}](#{})", escape_text(&block.title), heading_anchor(name));
+            }
+        }
+
+        match ctx.pages.get_relative_path(ctx.page_id, &block.id) {
+            Some(path) => format!(
+                "[{}]({})",
+                escape_text(&block.title),
+                encode_path(&path.to_string_lossy())
+            ),
+            None => block.title.clone(),
+        }
+    }
+
+    /// Links to the page the block is written on.
+    fn render_block_ref(&self, block: &Block, home_id: &str, ctx: &RenderContext) -> String {
+        let title = escape_text(&block.title);
+        if ctx.single_stream {
+            if let Some(name) = ctx.pages.get_name(home_id) {
+                return format!("[{}](#{})", title, heading_anchor(name));
+            }
+        }
+
+        match ctx.pages.get_relative_path(ctx.page_id, home_id) {
+            Some(path) => format!("[{}]({})", title, encode_path(&path.to_string_lossy())),
+            None => block.title.clone(),
+        }
+    }
+
+    fn render_date(&self, date: &Date, ctx: &RenderContext) -> String {
+        let text = date.to_iso_string();
+        let journal_id = match ctx.pages.get_journal(date) {
+            Some(journal_id) => journal_id,
+            None => return text,
+        };
+
+        if ctx.single_stream {
+            let name = ctx.pages.get_name(journal_id).unwrap_or(&text);
+            return format!("[{}](#{})", text, heading_anchor(name));
+        }
+
+        match ctx.pages.get_relative_path(ctx.page_id, journal_id) {
+            Some(path) => format!("[{}]({})", text, encode_path(&path.to_string_lossy())),
+            None => text,
+        }
+    }
+
+    fn supports_single_stream(&self) -> bool {
+        true
+    }
+
+    fn render_page(&self, block: &Block, ctx: &RenderContext) -> Vec<String> {
+        // Pages share one document when streamed, so headings need the unique
+        // page name for their anchors not to collide.
+        let title = match ctx.single_stream {
+            true => ctx.pages.get_name(&block.id).unwrap_or(&block.title),
+            false => &block.title,
+        };
+        let mut content = vec![format!("# {}", escape_text(title)), String::new()];
+
+        if !block.tags.is_empty() {
+            let tags = block
+                .tags
+                .iter()
+                .map(|tag| self.render_tag(tag))
+                .collect::<Vec<String>>()
+                .join(" ");
+            content.push(format!("Tags: {}", tags));
+            content.push(String::new());
+        }
+
+        let fields = block.metadata.iter().collect::<Vec<_>>();
+        content.append(&mut self.render_fields(&fields, block.description.as_ref(), 0, ctx));
+        content.append(&mut self.render_children(block, 0, ctx));
+        content.append(&mut self.render_backlinks(block, ctx));
+        content
+    }
+}
+
+fn escape_text(text: &str) -> String {
+    text.replace('[', "\\[").replace(']', "\\]")
+}
+
+/// Percent-encodes the characters that would end or break a link destination.
+fn encode_path(path: &str) -> String {
+    path.replace('\\', "/")
+        .chars()
+        .map(|c| match c {
+            ' ' | '(' | ')' | '<' | '>' | '%' | '#' | '?' | '"' => {
+                format!("%{:02X}", c as u32)
+            }
+            _ => c.to_string(),
+        })
+        .collect()
+}
+
+/// Anchor GitHub-flavoured renderers generate for a heading.
+fn heading_anchor(title: &str) -> String {
+    title
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+            _ => None,
+        })
+        .collect()
+}
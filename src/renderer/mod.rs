@@ -0,0 +1,321 @@
+use std::path::PathBuf;
+
+use crate::{
+    block::*,
+    config::{Config, LinkOptions},
+    date::Date,
+    node::{DocType, SearchTerm},
+    page_index::PageIndex,
+};
+
+mod logseq;
+mod markdown;
+mod obsidian;
+
+pub use logseq::LogseqRenderer;
+pub use markdown::MarkdownRenderer;
+pub use obsidian::ObsidianRenderer;
+
+pub const FORMATS: [&str; 3] = ["logseq", "obsidian", "markdown"];
+
+pub fn get_renderer(format: &str) -> Option<Box<dyn Renderer>> {
+    match format {
+        "logseq" => Some(Box::new(LogseqRenderer)),
+        "obsidian" => Some(Box::new(ObsidianRenderer::default())),
+        "markdown" => Some(Box::new(MarkdownRenderer)),
+        _ => None,
+    }
+}
+
+/// Like `get_renderer`, with the renderer set up from the config.
+pub fn from_config(config: &Config) -> Option<Box<dyn Renderer>> {
+    match config.format.as_str() {
+        "obsidian" => Some(Box::new(ObsidianRenderer::new(&config.daily_notes))),
+        format => get_renderer(format),
+    }
+}
+
+/// Everything a renderer needs to know about the page currently being written.
+pub struct RenderContext<'a> {
+    pub pages: &'a PageIndex,
+    pub page_id: &'a str,
+    /// All pages end up in one document instead of one file each.
+    pub single_stream: bool,
+    pub links: &'a LinkOptions,
+}
+
+pub trait Renderer {
+    fn render_link(&self, block: &Block, ctx: &RenderContext) -> String;
+
+    fn render_page(&self, block: &Block, ctx: &RenderContext) -> Vec<String>;
+
+    /// File the journal page of `date` is written to, relative to the output
+    /// directory.
+    fn get_journal_path(&self, date: &Date) -> PathBuf {
+        PathBuf::from(format!("journals/{}.md", date.to_iso_string()))
+    }
+
+    /// Name the target knows the journal page of `date` by.
+    fn get_journal_name(&self, date: &Date) -> String {
+        date.to_iso_string()
+    }
+
+    /// Whether the target organizes pages by namespaced names (`a/b/c`)
+    /// instead of folders.
+    fn uses_namespaces(&self) -> bool {
+        false
+    }
+
+    /// Renders a reference to a date, linking to its journal page.
+    fn render_date(&self, date: &Date, _ctx: &RenderContext) -> String {
+        date.to_iso_string()
+    }
+
+    /// Whether pages can be concatenated into a single document.
+    fn supports_single_stream(&self) -> bool {
+        false
+    }
+
+    /// Translates a Tana search into the target's query syntax, or `None` when
+    /// the expression has no faithful equivalent there.
+    fn render_query(&self, _terms: &[SearchTerm]) -> Option<Vec<String>> {
+        None
+    }
+
+    /// Renders a reference to a block that isn't a page. `home_id` is the page
+    /// the block is written on.
+    fn render_block_ref(&self, block: &Block, _home_id: &str, ctx: &RenderContext) -> String {
+        self.render_title(block, ctx)
+    }
+
+    /// Renders an inline reference to a block that isn't a page but has an id.
+    fn render_block_link(&self, block: &Block, home_id: &str, ctx: &RenderContext) -> String {
+        self.render_block_ref(block, home_id, ctx)
+    }
+
+    /// Attaches an id to the first line of a block that is referenced
+    /// elsewhere, so the references can point at it.
+    fn render_block_id(&self, line: String, _block: &Block, _level: usize) -> Vec<String> {
+        vec![line]
+    }
+
+    fn render_task_marker(&self, done: bool) -> String {
+        String::from(if done { "[x]" } else { "[ ]" })
+    }
+
+    fn render_tag(&self, tag: &str) -> String {
+        format!("#{}", tag)
+    }
+
+    fn render_title(&self, block: &Block, ctx: &RenderContext) -> String {
+        block
+            .inlines
+            .iter()
+            .map(|inline| match inline {
+                Inline::Text(text) => text.clone(),
+                Inline::Ref(target) => {
+                    let target = target.borrow();
+                    match ctx.pages.get_home(&target.id) {
+                        Some(home_id) if ctx.pages.has_block_id(&target.id) => {
+                            self.render_block_link(&target, home_id, ctx)
+                        }
+                        _ => self.render_link(&target, ctx),
+                    }
+                }
+                Inline::Date(date) => self.render_date(date, ctx),
+            })
+            .collect()
+    }
+
+    fn render_block(&self, block: &Block, level: usize, ctx: &RenderContext) -> Vec<String> {
+        let prefix = "  ".repeat(level);
+
+        if block.is_page() {
+            return vec![format!("{}- {}", prefix, self.render_link(block, ctx))];
+        }
+
+        if let Some(source) = &block.source {
+            let source = source.borrow();
+            let target = match ctx.pages.get_home(&source.id) {
+                _ if source.is_page() => self.render_link(&source, ctx),
+                Some(home_id) => self.render_block_ref(&source, home_id, ctx),
+                None => self.render_title(&source, ctx),
+            };
+            let mut content = vec![format!("{}- {}", prefix, target)];
+            content.append(&mut self.render_children(block, level + 1, ctx));
+            return content;
+        }
+
+        match block.doc_type {
+            DocType::Codeblock => return self.render_code_block(block, level),
+            DocType::Search => return self.render_search_block(block, level, ctx),
+            _ => {}
+        }
+
+        let mut parts = vec![];
+        if let Some(done) = block.todo {
+            parts.push(self.render_task_marker(done));
+        }
+        block
+            .tags
+            .iter()
+            .filter(|tag| block.todo.is_none() || tag != &"todo")
+            .for_each(|tag| parts.push(self.render_tag(tag)));
+        parts.push(self.render_title(block, ctx));
+
+        let line = format!("{}- {}", prefix, parts.join(" "));
+        let mut content = match ctx.pages.has_block_id(&block.id) {
+            true => self.render_block_id(line, block, level),
+            false => vec![line],
+        };
+        let fields = block.metadata.iter().collect::<Vec<_>>();
+        content.append(&mut self.render_fields(
+            &fields,
+            block.description.as_ref(),
+            level + 1,
+            ctx,
+        ));
+        content.append(&mut self.render_children(block, level + 1, ctx));
+        content
+    }
+
+    /// Renders the code as a fenced block that forms the content of a bullet,
+    /// so every line keeps its own indentation under the parent.
+    fn render_code_block(&self, block: &Block, level: usize) -> Vec<String> {
+        let prefix = "  ".repeat(level);
+        let longest_backtick_run = block
+            .title
+            .split(|c| c != '`')
+            .map(|run| run.len())
+            .max()
+            .unwrap_or(0);
+        let fence = "`".repeat(longest_backtick_run.max(2) + 1);
+        let language = block.language.as_deref().unwrap_or("");
+
+        let mut content = vec![format!("{}- {}{}", prefix, fence, language)];
+        block.title.lines().for_each(|line| {
+            if line.is_empty() {
+                content.push(String::new());
+            } else {
+                content.push(format!("{}  {}", prefix, line));
+            }
+        });
+        content.push(format!("{}  {}", prefix, fence));
+        content
+    }
+
+    /// Renders the search as a query when the target supports it, and as a
+    /// readable description otherwise. The results Tana stored with the search
+    /// are left out since the query reproduces them.
+    fn render_search_block(&self, block: &Block, level: usize, ctx: &RenderContext) -> Vec<String> {
+        let prefix = "  ".repeat(level);
+        let title = match block.title.is_empty() {
+            true => String::from("Search"),
+            false => self.render_title(block, ctx),
+        };
+        let mut content = vec![format!("{}- {}", prefix, title)];
+        let terms = block.search.as_deref().unwrap_or(&[]);
+
+        match self.render_query(terms) {
+            Some(lines) => lines.iter().enumerate().for_each(|(index, line)| {
+                let marker = if index == 0 { "- " } else { "  " };
+                content.push(format!("{}  {}{}", prefix, marker, line));
+            }),
+            None if terms.is_empty() => {}
+            None => {
+                let description = terms
+                    .iter()
+                    .map(|term| match term {
+                        SearchTerm::Tag(tag) => self.render_tag(tag),
+                        SearchTerm::Field(field, values) => {
+                            format!("{} is {}", field, values.join(" or "))
+                        }
+                        SearchTerm::Other(text) => format!("\"{}\"", text),
+                    })
+                    .collect::<Vec<String>>()
+                    .join(" AND ");
+                content.push(format!("{}  - Tana search: {}", prefix, description));
+            }
+        }
+
+        content
+    }
+
+    fn render_fields(
+        &self,
+        fields: &[&(String, Vec<BlockRef>)],
+        description: Option<&String>,
+        level: usize,
+        ctx: &RenderContext,
+    ) -> Vec<String> {
+        let mut content = vec![];
+        if fields.is_empty() && description.is_none() {
+            return content;
+        }
+
+        let prefix = "  ".repeat(level);
+        content.push(format!("{}- Metadata", prefix));
+        if let Some(description) = description {
+            content.push(format!("{}  - Description: {}", prefix, description));
+        }
+
+        fields.iter().for_each(|(key, values)| {
+            content.push(format!("{}  - {}", prefix, key));
+            let mut value_contents: Vec<String> = values
+                .iter()
+                .flat_map(|value| self.render_block(&value.borrow(), level + 2, ctx))
+                .collect();
+            content.append(&mut value_contents);
+        });
+
+        content
+    }
+
+    /// Lists the pages that reference the page, each with the bullets the
+    /// references appear in, when the export asks for it.
+    fn render_backlinks(&self, block: &Block, ctx: &RenderContext) -> Vec<String> {
+        let title = match &ctx.links.backlinks {
+            Some(title) => title,
+            None => return vec![],
+        };
+        let backlinks = ctx.pages.get_backlinks(&block.id);
+        if backlinks.is_empty() {
+            return vec![];
+        }
+
+        let mut page_ids: Vec<String> = vec![];
+        backlinks.iter().for_each(|backlink| {
+            let page_id = backlink.page.borrow().id.clone();
+            if !page_ids.contains(&page_id) {
+                page_ids.push(page_id);
+            }
+        });
+
+        let mut content = vec![format!("- {}", title)];
+        page_ids.iter().for_each(|page_id| {
+            let page_backlinks = backlinks
+                .iter()
+                .filter(|backlink| &backlink.page.borrow().id == page_id)
+                .collect::<Vec<_>>();
+            let page = page_backlinks[0].page.borrow();
+            content.push(format!("  - {}", self.render_link(&page, ctx)));
+
+            page_backlinks
+                .iter()
+                .filter_map(|backlink| backlink.context.as_ref())
+                .for_each(|context| {
+                    let title = self.render_title(&context.borrow(), ctx);
+                    content.push(format!("    - {}", title.replace('\n', " ")));
+                });
+        });
+        content
+    }
+
+    fn render_children(&self, block: &Block, level: usize, ctx: &RenderContext) -> Vec<String> {
+        block
+            .get_children()
+            .iter()
+            .flat_map(|child| self.render_block(&child.borrow(), level, ctx))
+            .collect()
+    }
+}
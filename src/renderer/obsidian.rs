@@ -0,0 +1,276 @@
+use std::{path::PathBuf, rc::Rc};
+
+use crate::{
+    block::*,
+    config::DailyNotes,
+    date::Date,
+    node::{DocType, SearchTerm},
+    page_index::sanitize_filename,
+};
+
+use super::{RenderContext, Renderer};
+
+pub struct ObsidianRenderer {
+    daily_notes_folder: String,
+    daily_notes_format: String,
+}
+
+impl Default for ObsidianRenderer {
+    fn default() -> Self {
+        ObsidianRenderer::new(&DailyNotes::default())
+    }
+}
+
+impl ObsidianRenderer {
+    pub fn new(daily_notes: &DailyNotes) -> ObsidianRenderer {
+        ObsidianRenderer {
+            daily_notes_folder: daily_notes.folder.clone(),
+            daily_notes_format: daily_notes.format.clone(),
+        }
+    }
+
+    fn render_frontmatter(&self, block: &Block, ctx: &RenderContext) -> Vec<String> {
+        let mut content = vec![String::from("---")];
+        content.push(format!("title: {}", yaml_string(&block.title)));
+
+        if !block.tags.is_empty() {
+            content.push(String::from("tags:"));
+            block
+                .tags
+                .iter()
+                .for_each(|tag| content.push(format!("  - {}", yaml_string(&tag_name(tag)))));
+        }
+
+        if let Some((_, values)) = block.metadata.iter().find(|(key, _)| is_alias_key(key)) {
+            content.push(String::from("aliases:"));
+            values.iter().for_each(|value| {
+                let value = value.borrow();
+                content.push(format!("  - {}", yaml_string(&value.title)));
+            });
+        }
+
+        content.push(format!(
+            "created: {}",
+            Date::from_timestamp(block.created).to_iso_string()
+        ));
+
+        if let Some(description) = &block.description {
+            content.push(format!("description: {}", yaml_string(description)));
+        }
+
+        block
+            .metadata
+            .iter()
+            .filter(|(key, values)| !is_alias_key(key) && is_frontmatter_field(values))
+            .for_each(|(key, values)| {
+                let values = values
+                    .iter()
+                    .map(|value| yaml_string(&self.render_scalar(&value.borrow(), ctx)))
+                    .collect::<Vec<String>>();
+
+                if values.len() == 1 {
+                    content.push(format!("{}: {}", yaml_key(key), values[0]));
+                } else {
+                    content.push(format!("{}:", yaml_key(key)));
+                    values
+                        .iter()
+                        .for_each(|value| content.push(format!("  - {}", value)));
+                }
+            });
+
+        content.push(String::from("---"));
+        content
+    }
+
+    fn render_scalar(&self, block: &Block, ctx: &RenderContext) -> String {
+        if let Some(source) = &block.source {
+            return self.render_scalar(&source.borrow(), ctx);
+        }
+
+        if block.is_page() {
+            self.render_link(block, ctx)
+        } else {
+            self.render_title(block, ctx)
+        }
+    }
+}
+
+impl Renderer for ObsidianRenderer {
+    /// Obsidian resolves links by file name, so the link targets the file and
+    /// shows the title when the two differ.
+    fn render_link(&self, block: &Block, ctx: &RenderContext) -> String {
+        let target = match ctx.pages.get_path(&block.id) {
+            Some(path) => path.with_extension("").to_string_lossy().replace('\\', "/"),
+            None => return format!("[[{}]]", block.title),
+        };
+
+        if target == block.title {
+            format!("[[{}]]", target)
+        } else {
+            format!(
+                "[[{}|{}]]",
+                target,
+                block.title.replace(['[', ']', '|'], "")
+            )
+        }
+    }
+
+    fn render_tag(&self, tag: &str) -> String {
+        format!("#{}", tag_name(tag))
+    }
+
+    /// Embeds the block from the page it is written on.
+    fn render_block_ref(&self, block: &Block, home_id: &str, ctx: &RenderContext) -> String {
+        match ctx.pages.get_path(home_id) {
+            Some(path) => format!(
+                "![[{}#^{}]]",
+                path.with_extension("").to_string_lossy().replace('\\', "/"),
+                block_id(&block.id)
+            ),
+            None => self.render_title(block, ctx),
+        }
+    }
+
+    fn render_block_link(&self, block: &Block, home_id: &str, ctx: &RenderContext) -> String {
+        match ctx.pages.get_path(home_id) {
+            Some(path) => format!(
+                "[[{}#^{}|{}]]",
+                path.with_extension("").to_string_lossy().replace('\\', "/"),
+                block_id(&block.id),
+                block.title.replace(['[', ']', '|'], "")
+            ),
+            None => self.render_link(block, ctx),
+        }
+    }
+
+    fn render_block_id(&self, line: String, block: &Block, _level: usize) -> Vec<String> {
+        vec![format!("{} ^{}", line, block_id(&block.id))]
+    }
+
+    /// Slashes in the date format create subfolders, as in Obsidian.
+    fn get_journal_path(&self, date: &Date) -> PathBuf {
+        let mut path: PathBuf = self
+            .daily_notes_folder
+            .split('/')
+            .filter(|folder| !folder.is_empty())
+            .map(sanitize_filename)
+            .collect();
+        date.format(&self.daily_notes_format)
+            .split('/')
+            .for_each(|part| path.push(sanitize_filename(part)));
+
+        path.set_extension("md");
+        path
+    }
+
+    fn get_journal_name(&self, date: &Date) -> String {
+        date.format(&self.daily_notes_format)
+    }
+
+    fn render_date(&self, date: &Date, _ctx: &RenderContext) -> String {
+        let target = self.get_journal_path(date).with_extension("");
+        format!("[[{}]]", target.to_string_lossy().replace('\\', "/"))
+    }
+
+    fn render_query(&self, terms: &[SearchTerm]) -> Option<Vec<String>> {
+        let mut tags = vec![];
+        let mut conditions = vec![];
+
+        for term in terms {
+            match term {
+                SearchTerm::Tag(tag) => tags.push(format!("#{}", tag_name(tag))),
+                SearchTerm::Field(field, values) => values.iter().for_each(|value| {
+                    conditions.push(format!(
+                        "contains(string(row[{}]), {})",
+                        yaml_string(field),
+                        yaml_string(value)
+                    ))
+                }),
+                SearchTerm::Other(_) => return None,
+            }
+        }
+
+        if tags.is_empty() && conditions.is_empty() {
+            return None;
+        }
+
+        let mut content = vec![String::from("```dataview"), String::from("LIST")];
+        if !tags.is_empty() {
+            content.push(format!("FROM {}", tags.join(" AND ")));
+        }
+        if !conditions.is_empty() {
+            content.push(format!("WHERE {}", conditions.join(" AND ")));
+        }
+        content.push(String::from("```"));
+        Some(content)
+    }
+
+    fn render_page(&self, block: &Block, ctx: &RenderContext) -> Vec<String> {
+        let mut content = self.render_frontmatter(block, ctx);
+
+        let fields = block
+            .metadata
+            .iter()
+            .filter(|(key, values)| !is_alias_key(key) && !is_frontmatter_field(values))
+            .collect::<Vec<_>>();
+        content.append(&mut self.render_fields(&fields, None, 0, ctx));
+        content.append(&mut self.render_children(block, 0, ctx));
+        content.append(&mut self.render_backlinks(block, ctx));
+        content
+    }
+}
+
+fn is_alias_key(key: &str) -> bool {
+    key.eq_ignore_ascii_case("aliases") || key.eq_ignore_ascii_case("alias")
+}
+
+/// A field can live in the frontmatter when every value is either a link to
+/// another page or a single line of text without children of its own.
+fn is_frontmatter_field(values: &[BlockRef]) -> bool {
+    values.iter().all(|value| {
+        let value = match &value.borrow().source {
+            Some(source) => Rc::clone(source),
+            None => Rc::clone(value),
+        };
+        let value = value.borrow();
+        if value.is_page() {
+            return true;
+        }
+
+        matches!(value.doc_type, DocType::Text)
+            && value.tags.is_empty()
+            && value.metadata.is_empty()
+            && value.get_children().is_empty()
+            && !value.title.contains('\n')
+    })
+}
+
+/// Obsidian block ids may only contain letters, digits and dashes.
+fn block_id(id: &str) -> String {
+    id.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect()
+}
+
+/// Obsidian tags may not contain whitespace.
+fn tag_name(tag: &str) -> String {
+    tag.split_whitespace().collect::<Vec<&str>>().join("-")
+}
+
+fn yaml_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn yaml_key(key: &str) -> String {
+    let is_plain = key.starts_with(|c: char| c.is_alphanumeric())
+        && !key.ends_with(' ')
+        && key
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == ' ');
+
+    if is_plain {
+        key.to_string()
+    } else {
+        yaml_string(key)
+    }
+}
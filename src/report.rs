@@ -0,0 +1,62 @@
+use std::{cell::RefCell, collections::HashSet, fs, path::Path};
+
+use crate::error::{Error, NodeIssue, Result, Stage};
+
+pub const REPORT_FILENAME: &str = "export-report.txt";
+
+pub struct SkippedNode {
+    pub id: String,
+    pub stage: Stage,
+    pub issue: NodeIssue,
+}
+
+/// Collects the nodes left out of a lenient export.
+#[derive(Default)]
+pub struct Report {
+    skipped: RefCell<Vec<SkippedNode>>,
+    skipped_ids: RefCell<HashSet<String>>,
+}
+
+impl Report {
+    pub fn new() -> Report {
+        Report::default()
+    }
+
+    pub fn skip(&self, id: &str, stage: Stage, issue: NodeIssue) {
+        if !self.skipped_ids.borrow_mut().insert(id.to_string()) {
+            return;
+        }
+
+        self.skipped.borrow_mut().push(SkippedNode {
+            id: id.to_string(),
+            stage,
+            issue,
+        });
+    }
+
+    pub fn is_skipped(&self, id: &str) -> bool {
+        self.skipped_ids.borrow().contains(id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.skipped.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.skipped.borrow().is_empty()
+    }
+
+    pub fn get_lines(&self) -> Vec<String> {
+        let mut lines = vec![format!("Skipped {} nodes", self.len()), String::new()];
+        self.skipped.borrow().iter().for_each(|node| {
+            lines.push(format!("{}\t{}\t{}", node.id, node.stage, node.issue));
+        });
+        lines
+    }
+
+    pub fn write(&self, output: &Path) -> Result<()> {
+        let lines = self.get_lines();
+        let filepath = output.join(REPORT_FILENAME);
+        fs::write(&filepath, lines.join("\n") + "\n").map_err(|err| Error::io(&filepath, err))
+    }
+}
@@ -0,0 +1,140 @@
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    config::{Config, Verbosity, WriteMode},
+    error::{Error, Result},
+};
+
+pub struct RenderedPage {
+    pub id: String,
+    /// Path of the page relative to the output directory.
+    pub path: PathBuf,
+    pub content: String,
+}
+
+/// Receives rendered pages one at a time.
+pub trait PageSink {
+    fn write_page(&mut self, page: RenderedPage) -> Result<()>;
+
+    /// Removes a page written by an earlier export, given its relative path.
+    fn remove_page(&mut self, _path: &Path) -> Result<()> {
+        Ok(())
+    }
+
+    /// Whether all pages end up in one document, so links must point inside
+    /// it rather than to other files.
+    fn is_single_stream(&self) -> bool {
+        false
+    }
+}
+
+impl PageSink for Vec<RenderedPage> {
+    fn write_page(&mut self, page: RenderedPage) -> Result<()> {
+        self.push(page);
+        Ok(())
+    }
+}
+
+/// Writes pages into the output directory of the config, honouring its write
+/// mode, dry-run and verbosity settings.
+pub struct DirSink<'a> {
+    config: &'a Config,
+    output: PathBuf,
+}
+
+impl<'a> DirSink<'a> {
+    pub fn new(config: &'a Config) -> Result<DirSink<'a>> {
+        let output = PathBuf::from(&config.output);
+
+        if !output.exists() && !config.dry_run {
+            fs::create_dir(&output).map_err(|err| Error::io(&output, err))?;
+        }
+
+        Ok(DirSink { config, output })
+    }
+}
+
+impl PageSink for DirSink<'_> {
+    fn write_page(&mut self, page: RenderedPage) -> Result<()> {
+        let filepath = self.output.join(&page.path);
+
+        if self.config.write_mode == WriteMode::Merge && filepath.exists() {
+            if self.config.verbosity >= Verbosity::Verbose {
+                println!("Kept {}", filepath.display());
+            }
+            return Ok(());
+        }
+
+        if self.config.dry_run {
+            if self.config.verbosity >= Verbosity::Normal {
+                println!("Would write {}", filepath.display());
+            }
+            return Ok(());
+        }
+
+        if let Some(parent) = filepath.parent() {
+            fs::create_dir_all(parent).map_err(|err| Error::io(parent, err))?;
+        }
+        fs::write(&filepath, page.content.as_bytes()).map_err(|err| Error::io(&filepath, err))?;
+
+        if self.config.verbosity >= Verbosity::Verbose {
+            println!("Wrote {}", filepath.display());
+        }
+        Ok(())
+    }
+
+    fn remove_page(&mut self, path: &Path) -> Result<()> {
+        let filepath = self.output.join(path);
+        if !filepath.is_file() {
+            return Ok(());
+        }
+
+        if self.config.dry_run {
+            if self.config.verbosity >= Verbosity::Normal {
+                println!("Would remove {}", filepath.display());
+            }
+            return Ok(());
+        }
+
+        fs::remove_file(&filepath).map_err(|err| Error::io(&filepath, err))?;
+
+        if self.config.verbosity >= Verbosity::Verbose {
+            println!("Removed {}", filepath.display());
+        }
+        Ok(())
+    }
+}
+
+/// Writes all pages one after another into a single stream such as stdout.
+pub struct StreamSink<W: Write> {
+    writer: W,
+    is_first: bool,
+}
+
+impl<W: Write> StreamSink<W> {
+    pub fn new(writer: W) -> StreamSink<W> {
+        StreamSink {
+            writer,
+            is_first: true,
+        }
+    }
+}
+
+impl<W: Write> PageSink for StreamSink<W> {
+    fn write_page(&mut self, page: RenderedPage) -> Result<()> {
+        let separator = if self.is_first { "" } else { "\n\n" };
+        self.is_first = false;
+
+        write!(self.writer, "{}{}", separator, page.content)
+            .and_then(|_| self.writer.flush())
+            .map_err(|err| Error::io("<stdout>", err))
+    }
+
+    fn is_single_stream(&self) -> bool {
+        true
+    }
+}
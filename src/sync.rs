@@ -0,0 +1,535 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    config::{Config, SyncStrategy, Verbosity},
+    error::{Error, Result},
+    manifest::{content_hash, Manifest},
+    sink::{PageSink, RenderedPage},
+};
+
+/// Copies of the last exported version of every page, kept as the common
+/// ancestor for three-way merges when syncing with `SyncStrategy::Merge`.
+pub const BASE_DIRNAME: &str = ".tana-to-markdown-base";
+
+const CONFLICT_EXTENSION: &str = "conflict";
+
+/// Largest number of line pairs compared when merging a page, so a huge page
+/// falls back to a conflict copy instead of exhausting memory.
+const MAX_MERGE_COMPARISONS: usize = 16_000_000;
+
+/// What a sync did with one file.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SyncOutcome {
+    Written,
+    /// Edited by hand, left as is.
+    Skipped,
+    /// Edited by hand, the export was written to the given file instead.
+    Conflict(PathBuf),
+    /// Edited by hand and merged with the export. `true` if some changes
+    /// clashed and were left between conflict markers.
+    Merged(bool),
+    Removed,
+    /// Edited by hand, so kept although the page is no longer exported.
+    Kept,
+}
+
+/// Writes pages into the output directory like `DirSink`, but leaves files
+/// edited since the last export to the configured `SyncStrategy`.
+pub struct SyncSink<'a> {
+    config: &'a Config,
+    strategy: SyncStrategy,
+    output: PathBuf,
+    previous: &'a Manifest,
+    outcomes: Vec<(PathBuf, SyncOutcome)>,
+}
+
+impl<'a> SyncSink<'a> {
+    pub fn new(
+        config: &'a Config,
+        strategy: SyncStrategy,
+        previous: &'a Manifest,
+    ) -> Result<SyncSink<'a>> {
+        let output = PathBuf::from(&config.output);
+
+        if !output.exists() && !config.dry_run {
+            fs::create_dir(&output).map_err(|err| Error::io(&output, err))?;
+        }
+
+        Ok(SyncSink {
+            config,
+            strategy,
+            output,
+            previous,
+            outcomes: vec![],
+        })
+    }
+
+    /// What happened to each file, by path relative to the output directory.
+    pub fn get_outcomes(&self) -> &[(PathBuf, SyncOutcome)] {
+        &self.outcomes
+    }
+
+    /// Reads the file at `path` if it differs from what the previous export
+    /// wrote there.
+    fn read_edited(&self, path: &Path) -> Result<Option<String>> {
+        let filepath = self.output.join(path);
+        if !filepath.is_file() {
+            return Ok(None);
+        }
+
+        let content = fs::read(&filepath).map_err(|err| Error::io(&filepath, err))?;
+        let is_edited = match self.previous.get_entry_by_path(path) {
+            Some(entry) => entry.hash != content_hash(&content),
+            // A file the export never wrote is not ours to replace.
+            None => true,
+        };
+
+        match is_edited {
+            true => Ok(Some(String::from_utf8_lossy(&content).to_string())),
+            false => Ok(None),
+        }
+    }
+
+    fn write(&self, path: &Path, content: &str) -> Result<()> {
+        if self.config.dry_run {
+            return Ok(());
+        }
+
+        let filepath = self.output.join(path);
+        if let Some(parent) = filepath.parent() {
+            fs::create_dir_all(parent).map_err(|err| Error::io(parent, err))?;
+        }
+        fs::write(&filepath, content.as_bytes()).map_err(|err| Error::io(&filepath, err))
+    }
+
+    fn remove(&self, path: &Path) -> Result<()> {
+        let filepath = self.output.join(path);
+        if self.config.dry_run || !filepath.is_file() {
+            return Ok(());
+        }
+
+        fs::remove_file(&filepath).map_err(|err| Error::io(&filepath, err))
+    }
+
+    fn record(&mut self, path: &Path, outcome: SyncOutcome) {
+        let verbosity = match outcome {
+            SyncOutcome::Written | SyncOutcome::Removed => Verbosity::Verbose,
+            _ => Verbosity::Normal,
+        };
+
+        if self.config.verbosity >= verbosity {
+            let prefix = if self.config.dry_run { "Would: " } else { "" };
+            println!(
+                "{}{}",
+                prefix,
+                describe_outcome(&self.output, path, &outcome)
+            );
+        }
+
+        self.outcomes.push((path.to_path_buf(), outcome));
+    }
+}
+
+impl PageSink for SyncSink<'_> {
+    fn write_page(&mut self, page: RenderedPage) -> Result<()> {
+        let base_path = Path::new(BASE_DIRNAME).join(&page.path);
+
+        let outcome = match (self.read_edited(&page.path)?, self.strategy) {
+            (None, _) => {
+                self.write(&page.path, &page.content)?;
+                SyncOutcome::Written
+            }
+            (Some(_), SyncStrategy::Skip) => SyncOutcome::Skipped,
+            (Some(edited), SyncStrategy::Merge) => {
+                let base = fs::read_to_string(self.output.join(&base_path)).ok();
+                match base.and_then(|base| merge_pages(&base, &edited, &page.content)) {
+                    Some((merged, has_conflicts)) => {
+                        self.write(&page.path, &merged)?;
+                        SyncOutcome::Merged(has_conflicts)
+                    }
+                    None => {
+                        let conflict_path = get_conflict_path(&page.path);
+                        self.write(&conflict_path, &page.content)?;
+                        SyncOutcome::Conflict(conflict_path)
+                    }
+                }
+            }
+            (Some(_), SyncStrategy::Conflict) => {
+                let conflict_path = get_conflict_path(&page.path);
+                self.write(&conflict_path, &page.content)?;
+                SyncOutcome::Conflict(conflict_path)
+            }
+        };
+
+        // Only merges read the base copy back.
+        if self.strategy == SyncStrategy::Merge {
+            self.write(&base_path, &page.content)?;
+        }
+        self.record(&page.path, outcome);
+        Ok(())
+    }
+
+    fn remove_page(&mut self, path: &Path) -> Result<()> {
+        let outcome = match self.read_edited(path)? {
+            Some(_) => SyncOutcome::Kept,
+            None => {
+                self.remove(path)?;
+                SyncOutcome::Removed
+            }
+        };
+
+        self.remove(&Path::new(BASE_DIRNAME).join(path))?;
+        self.record(path, outcome);
+        Ok(())
+    }
+}
+
+fn get_conflict_path(path: &Path) -> PathBuf {
+    let mut filename = path.as_os_str().to_os_string();
+    filename.push(".");
+    filename.push(CONFLICT_EXTENSION);
+    PathBuf::from(filename)
+}
+
+fn describe_outcome(output: &Path, path: &Path, outcome: &SyncOutcome) -> String {
+    let filepath = output.join(path);
+    let filepath = filepath.display();
+    match outcome {
+        SyncOutcome::Written => format!("Wrote {}", filepath),
+        SyncOutcome::Skipped => {
+            format!("Skipped {}, it was edited since the last export", filepath)
+        }
+        SyncOutcome::Conflict(path) => format!(
+            "Wrote {}, {} was edited since the last export",
+            output.join(path).display(),
+            filepath
+        ),
+        SyncOutcome::Merged(false) => format!("Merged the export into edited {}", filepath),
+        SyncOutcome::Merged(true) => format!(
+            "Merged the export into edited {}, resolve the conflict markers in it",
+            filepath
+        ),
+        SyncOutcome::Removed => format!("Removed {}", filepath),
+        SyncOutcome::Kept => format!(
+            "Kept {}, its page is gone but the file was edited since the last export",
+            filepath
+        ),
+    }
+}
+
+/// Three-way merges the bullet trees of two versions of a page against the
+/// version both started from. Every bullet, together with the lines that
+/// continue it, is merged as one unit, and its indentation keeps it in place
+/// in the tree. Returns the merged page and whether any changes clashed, or
+/// `None` if the page is too large to merge.
+pub fn merge_pages(base: &str, ours: &str, theirs: &str) -> Option<(String, bool)> {
+    let base = split_units(base);
+    let ours = split_units(ours);
+    let theirs = split_units(theirs);
+
+    let comparisons = base.len().saturating_mul(ours.len().max(theirs.len()));
+    if comparisons > MAX_MERGE_COMPARISONS {
+        return None;
+    }
+
+    let ours_matches = match_units(&base, &ours);
+    let theirs_matches = match_units(&base, &theirs);
+
+    let mut merged: Vec<&str> = vec![];
+    let mut has_conflicts = false;
+    let (mut b, mut o, mut t) = (0, 0, 0);
+
+    loop {
+        // The next unit left unchanged on both sides closes the current chunk.
+        let stable =
+            (b..base.len()).find_map(|index| match (ours_matches[index], theirs_matches[index]) {
+                (Some(o_index), Some(t_index)) => Some((index, o_index, t_index)),
+                _ => None,
+            });
+        let (b_end, o_end, t_end) = stable.unwrap_or((base.len(), ours.len(), theirs.len()));
+
+        let base_chunk = &base[b..b_end];
+        let ours_chunk = &ours[o..o_end];
+        let theirs_chunk = &theirs[t..t_end];
+
+        if ours_chunk == base_chunk || ours_chunk == theirs_chunk {
+            merged.extend(theirs_chunk);
+        } else if theirs_chunk == base_chunk {
+            merged.extend(ours_chunk);
+        } else {
+            has_conflicts = true;
+            merged.push("<<<<<<< edited");
+            merged.extend(ours_chunk);
+            merged.push("=======");
+            merged.extend(theirs_chunk);
+            merged.push(">>>>>>> export");
+        }
+
+        match stable {
+            Some(_) => {
+                merged.push(base[b_end]);
+                b = b_end + 1;
+                o = o_end + 1;
+                t = t_end + 1;
+            }
+            None => break,
+        }
+    }
+
+    Some((merged.join("\n"), has_conflicts))
+}
+
+/// Splits a page into a bullet with its continuation lines (such as the
+/// lines of a code block, blank ones included) per unit. Lines outside of
+/// bullets, like frontmatter or headings, are units of their own.
+fn split_units(content: &str) -> Vec<&str> {
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let indent_of = |line: &str| line.len() - line.trim_start().len();
+    let is_blank = |line: &str| line.trim().is_empty();
+
+    let mut units = vec![];
+    let mut start = 0;
+    let mut bullet_indent = None;
+    let mut offset = 0;
+
+    for (index, line) in lines.iter().enumerate() {
+        let text = line.trim_end_matches(['\n', '\r']);
+        let indent = indent_of(text);
+        let is_bullet = text.trim_start().starts_with("- ") || text.trim() == "-";
+        // A blank line continues the bullet when the bullet goes on after it.
+        let continues_bullet = !is_bullet
+            && bullet_indent.is_some_and(|bullet_indent| match is_blank(text) {
+                true => lines[index + 1..]
+                    .iter()
+                    .find(|line| !is_blank(line))
+                    .is_some_and(|next| {
+                        let next = next.trim_end_matches(['\n', '\r']);
+                        indent_of(next) > bullet_indent && !next.trim_start().starts_with("- ")
+                    }),
+                false => indent > bullet_indent,
+            });
+
+        if offset > start && !continues_bullet {
+            units.push(content[start..offset].trim_end_matches(['\n', '\r']));
+            start = offset;
+        }
+
+        bullet_indent = match (is_bullet, continues_bullet) {
+            (true, _) => Some(indent),
+            (false, true) => bullet_indent,
+            (false, false) => None,
+        };
+        offset += line.len();
+    }
+
+    if offset > start {
+        units.push(content[start..offset].trim_end_matches(['\n', '\r']));
+    }
+    units
+}
+
+/// Matches the units of `base` with those of `other` along their longest
+/// common subsequence, giving the index in `other` of every matched unit.
+fn match_units(base: &[&str], other: &[&str]) -> Vec<Option<usize>> {
+    let width = other.len() + 1;
+    let mut lengths = vec![0u32; (base.len() + 1) * width];
+
+    for i in (0..base.len()).rev() {
+        for j in (0..other.len()).rev() {
+            lengths[i * width + j] = match base[i] == other[j] {
+                true => lengths[(i + 1) * width + j + 1] + 1,
+                false => lengths[(i + 1) * width + j].max(lengths[i * width + j + 1]),
+            };
+        }
+    }
+
+    let mut matches = vec![None; base.len()];
+    let (mut i, mut j) = (0, 0);
+    while i < base.len() && j < other.len() {
+        if base[i] == other[j] {
+            matches[i] = Some(j);
+            i += 1;
+            j += 1;
+        } else if lengths[(i + 1) * width + j] >= lengths[i * width + j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::manifest::ManifestEntry;
+
+    use super::*;
+
+    const BASE: &str = "title:: Page\n- first\n- second\n  - nested\n- third";
+
+    #[test]
+    fn merge_keeps_edit_on_our_side() {
+        let ours = "title:: Page\n- first, edited\n- second\n  - nested\n- third";
+
+        let merged = merge_pages(BASE, ours, BASE);
+
+        assert_eq!(merged, Some((ours.to_string(), false)));
+    }
+
+    #[test]
+    fn merge_takes_edit_on_their_side() {
+        let theirs = "title:: Page\n- first\n- second\n  - nested\n  - added\n- third";
+
+        let merged = merge_pages(BASE, BASE, theirs);
+
+        assert_eq!(merged, Some((theirs.to_string(), false)));
+    }
+
+    #[test]
+    fn merge_combines_edits_to_different_bullets() {
+        let ours = "title:: Page\n- first, edited\n- second\n  - nested\n- third";
+        let theirs = "title:: Page\n- first\n- second\n  - nested\n- third, exported";
+
+        let merged = merge_pages(BASE, ours, theirs);
+
+        let expected = "title:: Page\n- first, edited\n- second\n  - nested\n- third, exported";
+        assert_eq!(merged, Some((expected.to_string(), false)));
+    }
+
+    #[test]
+    fn merge_marks_clashing_edits() {
+        let ours = "title:: Page\n- first\n- second, edited\n  - nested\n- third";
+        let theirs = "title:: Page\n- first\n- second, exported\n  - nested\n- third";
+
+        let merged = merge_pages(BASE, ours, theirs);
+
+        let expected = "title:: Page\n- first\n<<<<<<< edited\n- second, edited\n=======\n\
+                        - second, exported\n>>>>>>> export\n  - nested\n- third";
+        assert_eq!(merged, Some((expected.to_string(), true)));
+    }
+
+    #[test]
+    fn code_block_lines_belong_to_their_bullet() {
+        let page = "- ```rust\n  fn main() {\n\n  }\n  ```\n- after";
+
+        assert_eq!(
+            split_units(page),
+            vec!["- ```rust\n  fn main() {\n\n  }\n  ```", "- after"]
+        );
+    }
+
+    #[test]
+    fn merge_edits_code_block_as_one_unit() {
+        let base = "- before\n- between\n- ```rust\n  fn main() {}\n  ```\n- after";
+        let ours = "- before, edited\n- between\n- ```rust\n  fn main() {}\n  ```\n- after";
+        let theirs =
+            "- before\n- between\n- ```rust\n  fn main() {\n\n      run();\n  }\n  ```\n- after";
+
+        let merged = merge_pages(base, ours, theirs);
+
+        let expected =
+            "- before, edited\n- between\n- ```rust\n  fn main() {\n\n      run();\n  }\n  ```\n- after";
+        assert_eq!(merged, Some((expected.to_string(), false)));
+    }
+
+    #[test]
+    fn merge_marks_edits_to_adjacent_bullets() {
+        let ours = "title:: Page\n- first, edited\n- second\n  - nested\n- third";
+        let theirs = "title:: Page\n- first\n- second, exported\n  - nested\n- third";
+
+        let merged = merge_pages(BASE, ours, theirs);
+
+        assert!(merged.is_some_and(|(_, has_conflicts)| has_conflicts));
+    }
+
+    #[test]
+    fn merge_gives_up_on_pages_over_the_cap() {
+        let base = large_page("");
+        let ours = large_page("edited");
+        let theirs = large_page("exported");
+
+        assert_eq!(merge_pages(&base, &ours, &theirs), None);
+    }
+
+    #[test]
+    fn sync_writes_conflict_copy_when_page_is_too_large_to_merge() {
+        let dir =
+            std::env::temp_dir().join(format!("tana-to-markdown-sync-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("export.json");
+        fs::write(&input, "{}").unwrap();
+        let output = dir.join("out");
+
+        let args = [
+            input.to_string_lossy().to_string(),
+            output.to_string_lossy().to_string(),
+            String::from("--sync=merge"),
+            String::from("--quiet"),
+        ];
+        let config = Config::new(&args).unwrap();
+
+        let path = PathBuf::from("Page.md");
+        let base = large_page("");
+        fs::create_dir_all(output.join(BASE_DIRNAME)).unwrap();
+        fs::write(output.join(BASE_DIRNAME).join(&path), &base).unwrap();
+        fs::write(output.join(&path), large_page("edited")).unwrap();
+
+        let previous = Manifest {
+            format: String::from("logseq"),
+            options: 0,
+            pages: BTreeMap::from([(
+                String::from("page"),
+                ManifestEntry {
+                    path: path.clone(),
+                    modified: 0,
+                    links: vec![],
+                    blocks: 0,
+                    hash: content_hash(base.as_bytes()),
+                },
+            )]),
+        };
+
+        let mut sink = SyncSink::new(&config, SyncStrategy::Merge, &previous).unwrap();
+        sink.write_page(RenderedPage {
+            id: String::from("page"),
+            path: path.clone(),
+            content: large_page("exported"),
+        })
+        .unwrap();
+
+        let conflict_path = PathBuf::from("Page.md.conflict");
+        assert_eq!(
+            sink.get_outcomes(),
+            &[(path.clone(), SyncOutcome::Conflict(conflict_path.clone()))]
+        );
+        assert_eq!(
+            fs::read_to_string(output.join(&path)).unwrap(),
+            large_page("edited")
+        );
+        assert_eq!(
+            fs::read_to_string(output.join(&conflict_path)).unwrap(),
+            large_page("exported")
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// A page with enough bullets that merging it exceeds
+    /// `MAX_MERGE_COMPARISONS`, its middle bullet changed to `edit`.
+    fn large_page(edit: &str) -> String {
+        let lines = 4_100;
+        (0..lines)
+            .map(|line| match line == lines / 2 && !edit.is_empty() {
+                true => format!("- {}", edit),
+                false => format!("- line {}", line),
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}